@@ -1,6 +1,7 @@
-use darling::{ast, FromDeriveInput, FromField};
+use darling::{ast, FromDeriveInput, FromField, FromMeta, FromVariant};
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
 
 #[derive(Debug, Copy, Clone)]
 enum InputType {
@@ -15,149 +16,1479 @@ impl Default for InputType {
 }
 
 #[derive(Debug, FromDeriveInput)]
-#[darling(attributes(s2_grpc), supports(struct_named))]
+#[darling(
+  attributes(s2_grpc),
+  supports(struct_named, struct_newtype, struct_tuple, enum_any)
+)]
 pub struct InputReceiver {
   #[darling(skip)]
   input_type: InputType,
   ident: syn::Ident,
   generics: syn::Generics,
-  data: ast::Data<(), FieldReceiver>,
-  message_type: syn::Path,
+  data: ast::Data<VariantReceiver, FieldReceiver>,
+  #[darling(default)]
+  message_type: Option<syn::Path>,
+  /// Module holding a message named like the input, used when `message_type` is not set
+  #[darling(default)]
+  message_mod: Option<syn::Path>,
+  /// Path of the prost generated oneof enum, e.g. `shape_message::Kind`
+  #[darling(default)]
+  oneof_mod: Option<syn::Path>,
+  #[darling(default)]
+  pack_ref: bool,
+  #[darling(default)]
+  unpack_ref: bool,
+  #[darling(default)]
+  bound: Option<Bound>,
+  #[darling(default)]
+  collect_errors: bool,
+  #[darling(default)]
+  validate: Option<syn::Path>,
+  #[darling(default)]
+  variant_fields: bool,
+  #[darling(default)]
+  json: bool,
+  /// Fail to compile unless every field of the message is read when unpacking
+  #[darling(default)]
+  deny_unknown_proto_fields: bool,
+  /// Case of the proto field names, for fields without `rename` or `proto_name`
+  #[darling(default)]
+  rename_all: Option<RenameRule>,
 }
 
 impl InputReceiver {
+  pub fn validate(&self) -> darling::Result<()> {
+    if self.json {
+      if self.message_type.is_some()
+        || self.message_mod.is_some()
+        || self.oneof_mod.is_some()
+        || self.variant_fields
+        || self.validate.is_some()
+        || self.collect_errors
+        || self.deny_unknown_proto_fields
+        || self.rename_all.is_some()
+      {
+        return Err(darling::Error::custom(
+          "`json` can only be used together with `pack_ref`, `unpack_ref` and `bound`",
+        ));
+      }
+      return Ok(());
+    }
+    if let ast::Data::Enum(variants) = self.data.as_ref() {
+      let errors: Vec<_> = variants
+        .iter()
+        .filter(|v| v.fields.style != ast::Style::Tuple || v.fields.len() != 1)
+        .map(|v| {
+          darling::Error::custom(
+            "variants need exactly one unnamed field, use `json` to convert other enums through serde",
+          )
+          .with_span(&v.ident)
+        })
+        .collect();
+      if !errors.is_empty() {
+        return Err(darling::Error::multiple(errors));
+      }
+    }
+    let fields = match self.data.as_ref() {
+      ast::Data::Enum(_) if self.message_mod.is_some() && self.oneof_mod.is_some() => {
+        return Err(darling::Error::custom(
+          "`message_mod` and `oneof_mod` can not be used together",
+        ))
+      }
+      ast::Data::Enum(_) if self.message_type.is_some() && self.oneof_mod.is_some() => {
+        return Err(darling::Error::custom(
+          "`message_type` and `oneof_mod` can not be used together",
+        ))
+      }
+      ast::Data::Struct(_) if self.oneof_mod.is_some() => {
+        return Err(darling::Error::custom(
+          "`oneof_mod` can only be used on enums",
+        ))
+      }
+      ast::Data::Enum(_) if self.variant_fields && self.message_type.is_none() => {
+        return Err(darling::Error::custom(
+          "`variant_fields` needs the `message_type` holding the fields",
+        ))
+      }
+      ast::Data::Struct(_) if self.variant_fields => {
+        return Err(darling::Error::custom(
+          "`variant_fields` can only be used on enums",
+        ))
+      }
+      ast::Data::Enum(_) if self.validate.is_some() => {
+        return Err(darling::Error::custom(
+          "`validate` can only be used on structs",
+        ))
+      }
+      ast::Data::Enum(_) if self.rename_all.is_some() => {
+        return Err(darling::Error::custom(
+          "`rename_all` can only be used on structs",
+        ))
+      }
+      ast::Data::Enum(_) if self.deny_unknown_proto_fields => {
+        return Err(darling::Error::custom(
+          "`deny_unknown_proto_fields` can only be used on structs",
+        ))
+      }
+      ast::Data::Enum(_) if self.message_type.is_none() && self.oneof_mod.is_none() => {
+        return Err(darling::Error::missing_field("message_type"))
+      }
+      _ if self.validate.is_some() && self.message_type.is_none() => {
+        return Err(darling::Error::custom(
+          "`validate` can only be used together with `message_type`",
+        ))
+      }
+      _ if self.rename_all.is_some() && self.message_type.is_none() => {
+        return Err(darling::Error::custom(
+          "`rename_all` can only be used together with `message_type`",
+        ))
+      }
+      _ if self.deny_unknown_proto_fields && self.message_type.is_none() => {
+        return Err(darling::Error::custom(
+          "`deny_unknown_proto_fields` can only be used together with `message_type`",
+        ))
+      }
+      ast::Data::Enum(_) => return Ok(()),
+      ast::Data::Struct(fields) => fields,
+    };
+    if self.message_type.is_none() {
+      return match fields.fields.as_slice() {
+        [field] if fields.style == ast::Style::Tuple => field.validate_transparent(),
+        _ => Err(darling::Error::custom(
+          "missing `message_type`, it can only be omitted on newtype structs",
+        )),
+      };
+    }
+    let errors: Vec<_> = fields
+      .fields
+      .iter()
+      .flat_map(|f| f.validate(fields.style))
+      .collect();
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(darling::Error::multiple(errors))
+    }
+  }
+
+  /// Sets `message_type` to `message_mod::Ident` unless `message_type` is given
+  pub fn infer_message_type(mut self) -> Self {
+    if let (None, Some(message_mod)) = (self.message_type.as_ref(), self.message_mod.as_ref()) {
+      let mut path = message_mod.clone();
+      path.segments.push(self.ident.clone().into());
+      self.message_type = Some(path);
+    }
+    self
+  }
+
+  /// Records the position of each field, tuple struct fields are accessed by it
+  pub fn index_fields(mut self) -> Self {
+    if let ast::Data::Struct(ref mut fields) = self.data {
+      for (index, field) in fields.fields.iter_mut().enumerate() {
+        field.index = index;
+      }
+    }
+    self
+  }
+
+  /// Names the proto field of each named field by `rename_all`
+  pub fn rename_fields(mut self) -> Self {
+    if let (Some(rule), ast::Data::Struct(ref mut fields)) = (self.rename_all, &mut self.data) {
+      for field in fields.fields.iter_mut() {
+        if let Some(ident) = field.ident.as_ref() {
+          field.renamed = Some(rule.apply(ident));
+        }
+      }
+    }
+    self
+  }
+
   pub fn to_unpack(self) -> Self {
     Self {
       input_type: InputType::Unpack,
       ..self
     }
   }
-}
 
-impl ToTokens for InputReceiver {
-  fn to_tokens(&self, tokens: &mut TokenStream) {
+  /// Body of `unpack`/`unpack_ref`, reading from `value`,
+  /// runs the `validate` function on the unpacked struct
+  fn unpack_body(&self, fields: &[&FieldReceiver], by_ref: bool) -> TokenStream {
+    let body = self.unpack_fields(fields, by_ref);
+    let body = if self.deny_unknown_proto_fields {
+      let check = self.proto_fields_check(fields);
+      quote! { #check #body }
+    } else {
+      body
+    };
+    match self.validate.as_ref() {
+      Some(validate) => quote! {
+        let unpacked: Self = { #body }?;
+        #validate(&unpacked).map_err(|err| s2_grpc_utils::result::Error::Validation {
+          message: err.to_string(),
+        })?;
+        Ok(unpacked)
+      },
+      None => body,
+    }
+  }
+
+  /// An exhaustive pattern over the message, which does not compile if the message has
+  /// fields that no field of the struct reads
+  fn proto_fields_check(&self, fields: &[&FieldReceiver]) -> TokenStream {
+    let message_type = self.message_type.as_ref();
+    let mut proto_fields = flatten_groups(fields.iter().copied().filter(|f| !f.skip));
+    for f in fields {
+      if !f.skip
+        && f.compute.is_none()
+        && f.flatten.is_none()
+        && !proto_fields.contains(&f.proto_ident())
+      {
+        proto_fields.push(f.proto_ident());
+      }
+    }
+    quote_spanned! {self.ident.span()=>
+      let #message_type { #(#proto_fields: _,)* } = &value;
+    }
+  }
+
+  fn unpack_fields(&self, fields: &[&FieldReceiver], by_ref: bool) -> TokenStream {
+    let ident = &self.ident;
+    let bindings = flatten_bindings(fields, by_ref);
+    if fields.iter().any(|f| f.compute.is_some()) {
+      return self.unpack_computed(fields, by_ref, bindings);
+    }
+    if !self.collect_errors {
+      let lines = fields.iter().map(|f| f.unpack_tokens(by_ref));
+      return quote! {
+        #bindings
+        Ok(#ident {
+          #(#lines)*
+        })
+      };
+    }
+
+    let mut results = vec![];
+    let mut lines = vec![];
+    for f in fields {
+      let field_ident = f.member();
+      if let Some(result) = f.unpack_result(by_ref) {
+        let var = quote::format_ident!("__field{}", f.index);
+        results.push(quote! {
+          let #var = match #result {
+            Ok(v) => Some(v),
+            Err(err) => {
+              errors.push(err);
+              None
+            }
+          };
+        });
+        lines.push(quote! { #field_ident: #var.unwrap(), });
+      } else {
+        let value = f.unpack_value(by_ref);
+        lines.push(quote! { #field_ident: #value, });
+      }
+    }
+    quote! {
+      #bindings
+      let mut errors = vec![];
+      #(#results)*
+      if !errors.is_empty() {
+        return Err(s2_grpc_utils::result::Error::Multiple { errors });
+      }
+      Ok(#ident {
+        #(#lines)*
+      })
+    }
+  }
+
+  /// Same as `unpack_fields`, but binds every field to a local named after it in declaration
+  /// order, so `compute` expressions can reference the fields declared before them
+  fn unpack_computed(
+    &self,
+    fields: &[&FieldReceiver],
+    by_ref: bool,
+    bindings: TokenStream,
+  ) -> TokenStream {
+    let ident = &self.ident;
+    // Unpack into temporaries first, a field named `value` must not shadow the input
+    let mut unpacked = vec![];
+    let mut locals = vec![];
+    for f in fields {
+      let field_ident = f.member();
+      let var = quote::format_ident!("__field{}", f.index);
+      if let Some(compute) = f.compute.as_ref() {
+        locals.push(quote! { let #field_ident = #compute; });
+        continue;
+      }
+      match f.unpack_result(by_ref) {
+        Some(result) if self.collect_errors => {
+          unpacked.push(quote! {
+            let #var = match #result {
+              Ok(v) => Some(v),
+              Err(err) => {
+                errors.push(err);
+                None
+              }
+            };
+          });
+          locals.push(quote! { let #field_ident = #var.unwrap(); });
+        }
+        Some(result) => {
+          unpacked.push(quote! { let #var = #result?; });
+          locals.push(quote! { let #field_ident = #var; });
+        }
+        None => {
+          let value = f.unpack_value(by_ref);
+          unpacked.push(quote! { let #var = #value; });
+          locals.push(quote! { let #field_ident = #var; });
+        }
+      }
+    }
+    let check = if self.collect_errors {
+      quote! {
+        if !errors.is_empty() {
+          return Err(s2_grpc_utils::result::Error::Multiple { errors });
+        }
+      }
+    } else {
+      quote! {}
+    };
+    let errors = if self.collect_errors {
+      quote! { let mut errors = vec![]; }
+    } else {
+      quote! {}
+    };
+    let members = fields.iter().map(|f| f.member());
+    quote! {
+      #bindings
+      #errors
+      #(#unpacked)*
+      #check
+      #(#locals)*
+      Ok(#ident {
+        #(#members,)*
+      })
+    }
+  }
+
+  /// Body of `pack`/`pack_ref`, reading from `value`,
+  /// `flatten` fields are set on their sub-message after building the message
+  fn pack_body(&self, fields: &[&FieldReceiver], by_ref: bool) -> TokenStream {
+    let message_type = self.message_type.as_ref().unwrap();
+    let fields: Vec<_> = fields
+      .iter()
+      .copied()
+      .filter(|f| !f.skip && f.compute.is_none())
+      .collect();
+    let lines = fields
+      .iter()
+      .filter(|f| f.flatten.is_none())
+      .map(|f| f.pack_tokens(by_ref));
+    let groups = flatten_groups(fields.iter().copied());
+    if groups.is_empty() {
+      return quote! {
+        Ok(#message_type {
+          #(#lines)*
+        })
+      };
+    }
+
+    let assignments = fields.iter().filter_map(|f| {
+      let group = f.flatten.as_ref()?;
+      let value_field_ident = f.proto_ident();
+      let packed = f.pack_expr(by_ref);
+      Some(quote! {
+        packed.#group.get_or_insert_with(Default::default).#value_field_ident = #packed;
+      })
+    });
+    quote! {
+      let mut packed = #message_type {
+        #(#lines)*
+        #(#groups: Default::default(),)*
+      };
+      #(#assignments)*
+      Ok(packed)
+    }
+  }
+
+  /// Generics of the input with the `bound` predicates appended to the where clause
+  fn impl_generics(&self) -> syn::Generics {
+    let mut generics = self.generics.clone();
+    if let Some(bound) = self.bound.as_ref() {
+      generics
+        .make_where_clause()
+        .predicates
+        .extend(bound.0.iter().cloned());
+    }
+    generics
+  }
+
+  /// Newtype structs without `message_type` convert like the wrapped type,
+  /// to whatever proto type it supports
+  fn transparent_to_tokens(&self, field: &FieldReceiver, tokens: &mut TokenStream) {
+    let ident = &self.ident;
+    let inner = &field.ty;
+    let mut generics = self.impl_generics();
+    generics.params.push(syn::parse_quote!(__P));
+    let (imp, _, _) = generics.split_for_impl();
+    let (_, ty, _) = self.generics.split_for_impl();
+    let predicates: Vec<_> = generics
+      .where_clause
+      .iter()
+      .flat_map(|w| w.predicates.iter())
+      .collect();
+    let bounded = |bound: TokenStream| {
+      quote! { where #(#predicates,)* #inner: #bound }
+    };
+
+    match self.input_type {
+      InputType::Pack => {
+        let wher = bounded(quote! { s2_grpc_utils::S2ProtoPack<__P> });
+        tokens.extend(quote! {
+          impl #imp s2_grpc_utils::S2ProtoPack<__P> for #ident #ty #wher {
+            fn pack(self) -> s2_grpc_utils::result::Result<__P> {
+              s2_grpc_utils::S2ProtoPack::pack(self.0)
+            }
+          }
+        });
+        if self.pack_ref {
+          let wher = bounded(quote! { s2_grpc_utils::S2ProtoPackRef<__P> });
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoPackRef<__P> for #ident #ty #wher {
+              fn pack_ref(&self) -> s2_grpc_utils::result::Result<__P> {
+                s2_grpc_utils::S2ProtoPackRef::pack_ref(&self.0)
+              }
+            }
+          });
+        }
+      }
+      InputType::Unpack => {
+        let wher = bounded(quote! { s2_grpc_utils::S2ProtoUnpack<__P> });
+        tokens.extend(quote! {
+          impl #imp s2_grpc_utils::S2ProtoUnpack<__P> for #ident #ty #wher {
+            fn unpack(value: __P) -> s2_grpc_utils::result::Result<Self> {
+              <#inner as s2_grpc_utils::S2ProtoUnpack<__P>>::unpack(value).map(Self)
+            }
+          }
+        });
+        if self.unpack_ref {
+          let wher = bounded(quote! { s2_grpc_utils::S2ProtoUnpackRef<__P> });
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoUnpackRef<__P> for #ident #ty #wher {
+              fn unpack_ref(value: &__P) -> s2_grpc_utils::result::Result<Self> {
+                <#inner as s2_grpc_utils::S2ProtoUnpackRef<__P>>::unpack_ref(value).map(Self)
+              }
+            }
+          });
+        }
+      }
+    }
+  }
+
+  /// Converts through `Json<Self>`, to any proto type `Json` supports
+  fn json_to_tokens(&self, tokens: &mut TokenStream) {
+    let ident = &self.ident;
+    let mut generics = self.impl_generics();
+    generics.params.push(syn::parse_quote!(__P));
+    let (imp, _, _) = generics.split_for_impl();
+    let (_, ty, _) = self.generics.split_for_impl();
+    let predicates: Vec<_> = generics
+      .where_clause
+      .iter()
+      .flat_map(|w| w.predicates.iter())
+      .collect();
+    let json = quote! { s2_grpc_utils::Json<#ident #ty> };
+
+    match self.input_type {
+      InputType::Pack => {
+        tokens.extend(quote! {
+          impl #imp s2_grpc_utils::S2ProtoPack<__P> for #ident #ty
+          where #(#predicates,)* #json: s2_grpc_utils::S2ProtoPack<__P>
+          {
+            fn pack(self) -> s2_grpc_utils::result::Result<__P> {
+              s2_grpc_utils::S2ProtoPack::pack(s2_grpc_utils::Json(self))
+            }
+          }
+        });
+        if self.pack_ref {
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoPackRef<__P> for #ident #ty
+            where
+              #(#predicates,)*
+              for<'__a> s2_grpc_utils::Json<&'__a #ident #ty>: s2_grpc_utils::S2ProtoPackRef<__P>
+            {
+              fn pack_ref(&self) -> s2_grpc_utils::result::Result<__P> {
+                s2_grpc_utils::S2ProtoPackRef::pack_ref(&s2_grpc_utils::Json(self))
+              }
+            }
+          });
+        }
+      }
+      InputType::Unpack => {
+        tokens.extend(quote! {
+          impl #imp s2_grpc_utils::S2ProtoUnpack<__P> for #ident #ty
+          where #(#predicates,)* #json: s2_grpc_utils::S2ProtoUnpack<__P>
+          {
+            fn unpack(value: __P) -> s2_grpc_utils::result::Result<Self> {
+              <#json as s2_grpc_utils::S2ProtoUnpack<__P>>::unpack(value).map(|json| json.0)
+            }
+          }
+        });
+        if self.unpack_ref {
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoUnpackRef<__P> for #ident #ty
+            where #(#predicates,)* #json: s2_grpc_utils::S2ProtoUnpackRef<__P>
+            {
+              fn unpack_ref(value: &__P) -> s2_grpc_utils::result::Result<Self> {
+                <#json as s2_grpc_utils::S2ProtoUnpackRef<__P>>::unpack_ref(value).map(|json| json.0)
+              }
+            }
+          });
+        }
+      }
+    }
+  }
+
+  /// Maps each newtype variant to the variant of the prost generated oneof enum with the same name,
+  /// or to a field of `message_type` with `variant_fields`
+  fn oneof_to_tokens(&self, variants: &[&VariantReceiver], tokens: &mut TokenStream) {
     let InputReceiver {
       input_type,
       ref ident,
-      ref generics,
-      ref data,
-      ref message_type,
+      ..
     } = *self;
+    let message_type = self
+      .message_type
+      .as_ref()
+      .or(self.oneof_mod.as_ref())
+      .unwrap();
 
+    let generics = self.impl_generics();
     let (imp, ty, wher) = generics.split_for_impl();
-    let fields = data
-      .as_ref()
-      .take_struct()
-      .expect("Should never be enum")
-      .fields;
 
     match input_type {
       InputType::Pack => {
-        let pack_lines: Vec<_> = fields
-          .iter()
-          .map(|f| {
-            let field_ident = &f.ident;
-            let value_field_ident = if let Some(ident) = f.rename.as_ref() {
-              ident
-            } else {
-              f.ident.as_ref().unwrap()
-            };
-            if let Some(map_fn) = f.map_fn.as_ref() {
-              quote! {
-                #value_field_ident: #map_fn(value.#field_ident),
+        let pack_body = self.pack_variants(variants, false);
+        tokens.extend(quote! {
+          impl #imp s2_grpc_utils::S2ProtoPack<#message_type> for #ident #ty #wher {
+            fn pack(self) -> s2_grpc_utils::result::Result<#message_type> {
+              #pack_body
+            }
+          }
+
+          impl #imp s2_grpc_utils::S2ProtoPack<Option<#message_type>> for #ident #ty #wher {
+            fn pack(self) -> s2_grpc_utils::result::Result<Option<#message_type>> {
+              <Self as s2_grpc_utils::S2ProtoPack<#message_type>>::pack(self).map(Some)
+            }
+          }
+        });
+
+        if self.pack_ref {
+          let pack_ref_body = self.pack_variants(variants, true);
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoPackRef<#message_type> for #ident #ty #wher {
+              fn pack_ref(&self) -> s2_grpc_utils::result::Result<#message_type> {
+                #pack_ref_body
+              }
+            }
+
+            impl #imp s2_grpc_utils::S2ProtoPackRef<Option<#message_type>> for #ident #ty #wher {
+              fn pack_ref(&self) -> s2_grpc_utils::result::Result<Option<#message_type>> {
+                <Self as s2_grpc_utils::S2ProtoPackRef<#message_type>>::pack_ref(self).map(Some)
+              }
+            }
+          })
+        }
+      }
+      InputType::Unpack => {
+        let unpack_body = self.unpack_variants(variants, false);
+        tokens.extend(quote! {
+          impl #imp s2_grpc_utils::S2ProtoUnpack<#message_type> for #ident #ty #wher {
+            fn unpack(value: #message_type) -> s2_grpc_utils::result::Result<Self> {
+              #unpack_body
+            }
+          }
+
+          impl #imp s2_grpc_utils::S2ProtoUnpack<Option<#message_type>> for #ident #ty #wher {
+            fn unpack(value: Option<#message_type>) -> s2_grpc_utils::result::Result<Self> {
+              if let Some(value) = value {
+                <Self as s2_grpc_utils::S2ProtoUnpack<#message_type>>::unpack(value)
+              } else {
+                Err(s2_grpc_utils::result::Error::ValueNotPresent)
+              }
+            }
+          }
+        });
+
+        if self.unpack_ref {
+          let unpack_ref_body = self.unpack_variants(variants, true);
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoUnpackRef<#message_type> for #ident #ty #wher {
+              fn unpack_ref(value: &#message_type) -> s2_grpc_utils::result::Result<Self> {
+                #unpack_ref_body
               }
-            } else {
-              quote! {
-                #value_field_ident: value.#field_ident.pack()?,
+            }
+
+            impl #imp s2_grpc_utils::S2ProtoUnpackRef<Option<#message_type>> for #ident #ty #wher {
+              fn unpack_ref(value: &Option<#message_type>) -> s2_grpc_utils::result::Result<Self> {
+                if let Some(value) = value {
+                  <Self as s2_grpc_utils::S2ProtoUnpackRef<#message_type>>::unpack_ref(value)
+                } else {
+                  Err(s2_grpc_utils::result::Error::ValueNotPresent)
+                }
               }
             }
           })
-          .collect();
+        }
+      }
+    }
+  }
+
+  /// Body of `pack`/`pack_ref` of an enum, matching on `self`
+  fn pack_variants(&self, variants: &[&VariantReceiver], by_ref: bool) -> TokenStream {
+    let ident = &self.ident;
+    let message_type = self
+      .message_type
+      .as_ref()
+      .or(self.oneof_mod.as_ref())
+      .unwrap();
+    let pack = if by_ref {
+      quote! { s2_grpc_utils::S2ProtoPackRef::pack_ref(v)? }
+    } else {
+      quote! { s2_grpc_utils::S2ProtoPack::pack(v)? }
+    };
+    if self.variant_fields {
+      let arms = variants.iter().map(|v| {
+        let v_ident = &v.ident;
+        let field_ident = v.field_ident();
+        quote! {
+          #ident::#v_ident(v) => packed.#field_ident = #pack,
+        }
+      });
+      return quote! {
+        let mut packed = <#message_type as Default>::default();
+        match self {
+          #(#arms)*
+        }
+        Ok(packed)
+      };
+    }
+    let arms = variants.iter().map(|v| {
+      let v_ident = &v.ident;
+      let proto_ident = v.proto_ident();
+      quote! {
+        #ident::#v_ident(v) => #message_type::#proto_ident(#pack),
+      }
+    });
+    quote! {
+      Ok(match self {
+        #(#arms)*
+      })
+    }
+  }
+
+  /// Body of `unpack`/`unpack_ref` of an enum, reading from `value`.
+  /// With `variant_fields`, the variant is picked by the only field not set to its default
+  /// value, setting none or several of them is an error
+  fn unpack_variants(&self, variants: &[&VariantReceiver], by_ref: bool) -> TokenStream {
+    let ident = &self.ident;
+    let message_type = self
+      .message_type
+      .as_ref()
+      .or(self.oneof_mod.as_ref())
+      .unwrap();
+    if self.variant_fields {
+      let checks = variants.iter().map(|v| {
+        let field_ident = v.field_ident();
+        quote! {
+          if is_set(&value.#field_ident) {
+            set.push(stringify!(#field_ident));
+          }
+        }
+      });
+      let arms = variants.iter().map(|v| {
+        let v_ident = &v.ident;
+        let field_ident = v.field_ident();
+        let unpack = if by_ref {
+          quote! { s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(&value.#field_ident) }
+        } else {
+          quote! { s2_grpc_utils::S2ProtoUnpack::unpack(value.#field_ident) }
+        };
+        quote! {
+          [field] if *field == stringify!(#field_ident) => Ok(#ident::#v_ident(
+            #unpack.map_err(|err| err.at(stringify!(#field_ident)))?,
+          )),
+        }
+      });
+      return quote! {
+        fn is_set<T: Default + PartialEq>(v: &T) -> bool {
+          *v != T::default()
+        }
+        let mut set: Vec<&'static str> = vec![];
+        #(#checks)*
+        match set.as_slice() {
+          [] => Err(s2_grpc_utils::result::Error::ValueNotPresent),
+          #(#arms)*
+          _ => Err(s2_grpc_utils::result::Error::MultipleVariantFields { fields: set }),
+        }
+      };
+    }
+    let arms = variants.iter().map(|v| {
+      let v_ident = &v.ident;
+      let proto_ident = v.proto_ident();
+      let unpack = if by_ref {
+        quote! { s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(v)? }
+      } else {
+        quote! { s2_grpc_utils::S2ProtoUnpack::unpack(v)? }
+      };
+      quote! {
+        #message_type::#proto_ident(v) => #ident::#v_ident(#unpack),
+      }
+    });
+    quote! {
+      Ok(match value {
+        #(#arms)*
+      })
+    }
+  }
+}
+
+impl ToTokens for InputReceiver {
+  fn to_tokens(&self, tokens: &mut TokenStream) {
+    let InputReceiver {
+      input_type,
+      ref ident,
+      ref data,
+      pack_ref,
+      unpack_ref,
+      ..
+    } = *self;
+
+    if self.json {
+      return self.json_to_tokens(tokens);
+    }
+    let generics = self.impl_generics();
+    let (imp, ty, wher) = generics.split_for_impl();
+    let fields = match data.as_ref() {
+      ast::Data::Enum(variants) => return self.oneof_to_tokens(&variants, tokens),
+      ast::Data::Struct(fields) => fields.fields,
+    };
+    let message_type = match self.message_type {
+      Some(ref message_type) => message_type,
+      None => return self.transparent_to_tokens(fields[0], tokens),
+    };
+
+    match input_type {
+      InputType::Pack => {
+        let pack_body = self.pack_body(&fields, false);
         tokens.extend(quote! {
           impl #imp s2_grpc_utils::S2ProtoPack<#message_type> for #ident #ty #wher {
             fn pack(self) -> s2_grpc_utils::result::Result<#message_type> {
               let value = self;
-              Ok(#message_type {
-                #(#pack_lines)*
-              })
+              #pack_body
             }
           }
 
           impl #imp s2_grpc_utils::S2ProtoPack<Option<#message_type>> for #ident #ty #wher {
             fn pack(self) -> s2_grpc_utils::result::Result<Option<#message_type>> {
-              let value = self;
-              Ok(Some(#message_type {
-                #(#pack_lines)*
-              }))
+              <Self as s2_grpc_utils::S2ProtoPack<#message_type>>::pack(self).map(Some)
             }
           }
-        })
-      }
-      InputType::Unpack => {
-        let unpack_lines: Vec<_> = fields
-          .iter()
-          .map(|f| {
-            let field_ident = &f.ident;
-            let value_field_ident = if let Some(ident) = f.rename.as_ref() {
-              ident
-            } else {
-              f.ident.as_ref().unwrap()
-            };
-            let field_expr = if let Some(map_fn) = f.map_fn.as_ref() {
-              quote! {
-                #map_fn(value.#value_field_ident)
+        });
+
+        if pack_ref {
+          let pack_ref_body = self.pack_body(&fields, true);
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoPackRef<#message_type> for #ident #ty #wher {
+              fn pack_ref(&self) -> s2_grpc_utils::result::Result<#message_type> {
+                let value = self;
+                #pack_ref_body
               }
-            } else {
-              quote! {
-                S2ProtoUnpack::unpack(value.#value_field_ident).map_err(|err| {
-                  if let s2_grpc_utils::result::Error::ValueNotPresent = err {
-                    s2_grpc_utils::result::Error::FieldValueNotPresent {
-                      field_name: stringify!(#field_ident),
-                    }
-                  } else {
-                    err
-                  }
-                })?
+            }
+
+            impl #imp s2_grpc_utils::S2ProtoPackRef<Option<#message_type>> for #ident #ty #wher {
+              fn pack_ref(&self) -> s2_grpc_utils::result::Result<Option<#message_type>> {
+                <Self as s2_grpc_utils::S2ProtoPackRef<#message_type>>::pack_ref(self).map(Some)
               }
-            };
-            quote! {
-              #field_ident: #field_expr,
             }
           })
-          .collect();
+        }
+      }
+      InputType::Unpack => {
+        let unpack_body = self.unpack_body(&fields, false);
         tokens.extend(quote! {
           impl #imp s2_grpc_utils::S2ProtoUnpack<#message_type> for #ident #ty #wher {
-            fn unpack(value: #message_type) -> s2_grpc_utils::result::Result<#ident> {
-              Ok(#ident {
-                #(#unpack_lines)*
-              })
+            fn unpack(value: #message_type) -> s2_grpc_utils::result::Result<Self> {
+              #unpack_body
             }
           }
 
           impl #imp s2_grpc_utils::S2ProtoUnpack<Option<#message_type>> for #ident #ty #wher {
-            fn unpack(value: Option<#message_type>) -> s2_grpc_utils::result::Result<#ident> {
+            fn unpack(value: Option<#message_type>) -> s2_grpc_utils::result::Result<Self> {
               if let Some(value) = value {
-                Ok(#ident {
-                  #(#unpack_lines)*
-                })
+                <Self as s2_grpc_utils::S2ProtoUnpack<#message_type>>::unpack(value)
               } else {
                 Err(s2_grpc_utils::result::Error::ValueNotPresent)
               }
             }
           }
-        })
+        });
+
+        if unpack_ref {
+          let unpack_ref_body = self.unpack_body(&fields, true);
+          tokens.extend(quote! {
+            impl #imp s2_grpc_utils::S2ProtoUnpackRef<#message_type> for #ident #ty #wher {
+              fn unpack_ref(value: &#message_type) -> s2_grpc_utils::result::Result<Self> {
+                #unpack_ref_body
+              }
+            }
+
+            impl #imp s2_grpc_utils::S2ProtoUnpackRef<Option<#message_type>> for #ident #ty #wher {
+              fn unpack_ref(value: &Option<#message_type>) -> s2_grpc_utils::result::Result<Self> {
+                if let Some(value) = value {
+                  <Self as s2_grpc_utils::S2ProtoUnpackRef<#message_type>>::unpack_ref(value)
+                } else {
+                  Err(s2_grpc_utils::result::Error::ValueNotPresent)
+                }
+              }
+            }
+          })
+        }
       }
     }
   }
 }
 
+/// Distinct sub-message fields of the `flatten` fields, in order of appearance
+fn flatten_groups<'a>(fields: impl Iterator<Item = &'a FieldReceiver>) -> Vec<&'a syn::Ident> {
+  let mut groups = vec![];
+  for group in fields.filter_map(|f| f.flatten.as_ref()) {
+    if !groups.contains(&group) {
+      groups.push(group);
+    }
+  }
+  groups
+}
+
+/// Binds each sub-message of the `flatten` fields, a missing sub-message is an error
+fn flatten_bindings(fields: &[&FieldReceiver], by_ref: bool) -> TokenStream {
+  let bindings = flatten_groups(fields.iter().filter(|f| !f.skip).copied())
+    .into_iter()
+    .map(|group| {
+      let var = quote::format_ident!("__flatten_{}", group);
+      let sub = if by_ref {
+        quote! { &value.#group }
+      } else {
+        quote! { value.#group }
+      };
+      quote! {
+        let #var = match #sub {
+          Some(v) => v,
+          None => {
+            return Err(s2_grpc_utils::result::Error::FieldValueNotPresent {
+              field_name: stringify!(#group),
+            })
+          }
+        };
+      }
+    });
+  quote! { #(#bindings)* }
+}
+
+/// Whether the type is spelled as `Option<..>`, aliases can't be seen by the derive
+fn is_option(ty: &syn::Type) -> bool {
+  match ty {
+    syn::Type::Path(path) => path
+      .path
+      .segments
+      .last()
+      .map_or(false, |segment| segment.ident == "Option"),
+    _ => false,
+  }
+}
+
 #[derive(Debug, FromField)]
 #[darling(attributes(s2_grpc))]
 struct FieldReceiver {
   ident: Option<syn::Ident>,
   ty: syn::Type,
+  #[darling(skip)]
+  index: usize,
+  /// Proto field name from the container's `rename_all`
+  #[darling(skip)]
+  renamed: Option<syn::Ident>,
   #[darling(default)]
   rename: Option<syn::Ident>,
   #[darling(default)]
+  proto_name: Option<syn::Ident>,
+  #[darling(default)]
   map_fn: Option<syn::Path>,
+  #[darling(default)]
+  with: Option<syn::Path>,
+  #[darling(default)]
+  skip: bool,
+  #[darling(default)]
+  default: Option<Expr>,
+  #[darling(default)]
+  required: bool,
+  #[darling(default)]
+  optional: bool,
+  #[darling(default)]
+  or_default: bool,
+  #[darling(default)]
+  pack_default: bool,
+  /// The proto field is an enum stored as `i32`. Enums convert from/to `i32` anyway,
+  /// so this only spells out `none_on_unspecified`, which implies it
+  #[darling(default, rename = "enum")]
+  proto_enum: bool,
+  /// The proto field is an enum stored as `i32`, its zero discriminant is `None`
+  /// of an `Option` field
+  #[darling(default)]
+  none_on_unspecified: bool,
+  #[darling(default)]
+  require_nonzero: bool,
+  #[darling(default)]
+  flatten: Option<syn::Ident>,
+  #[darling(default)]
+  compute: Option<Expr>,
+  #[darling(default)]
+  from: Option<Type>,
+}
+
+/// An expression passed as a string literal, e.g. `default = "Vec::new()"`
+#[derive(Debug)]
+struct Expr(syn::Expr);
+
+impl FromMeta for Expr {
+  fn from_string(value: &str) -> darling::Result<Self> {
+    syn::parse_str(value)
+      .map(Expr)
+      .map_err(|_| darling::Error::unknown_value(value))
+  }
+}
+
+impl ToTokens for Expr {
+  fn to_tokens(&self, tokens: &mut TokenStream) {
+    self.0.to_tokens(tokens)
+  }
+}
+
+/// A type passed as a string literal, e.g. `from = "u64"`
+#[derive(Debug)]
+struct Type(syn::Type);
+
+impl FromMeta for Type {
+  fn from_string(value: &str) -> darling::Result<Self> {
+    syn::parse_str(value)
+      .map(Type)
+      .map_err(|_| darling::Error::unknown_value(value))
+  }
+}
+
+impl ToTokens for Type {
+  fn to_tokens(&self, tokens: &mut TokenStream) {
+    self.0.to_tokens(tokens)
+  }
+}
+
+/// Extra where predicates for the generated impls, e.g. `bound = "T: Clone"`
+#[derive(Debug)]
+struct Bound(Vec<syn::WherePredicate>);
+
+impl FromMeta for Bound {
+  fn from_string(value: &str) -> darling::Result<Self> {
+    use syn::parse::Parser;
+
+    syn::punctuated::Punctuated::<syn::WherePredicate, syn::Token![,]>::parse_terminated
+      .parse_str(value)
+      .map(|predicates| Bound(predicates.into_iter().collect()))
+      .map_err(|_| darling::Error::unknown_value(value))
+  }
+}
+
+/// Case conversion of `rename_all`, from the snake_case Rust field names
+#[derive(Debug, Clone, Copy)]
+enum RenameRule {
+  CamelCase,
+  PascalCase,
+  ScreamingSnakeCase,
+}
+
+impl FromMeta for RenameRule {
+  fn from_string(value: &str) -> darling::Result<Self> {
+    match value {
+      "camelCase" => Ok(RenameRule::CamelCase),
+      "PascalCase" => Ok(RenameRule::PascalCase),
+      "SCREAMING_SNAKE_CASE" => Ok(RenameRule::ScreamingSnakeCase),
+      _ => Err(darling::Error::unknown_value(value)),
+    }
+  }
+}
+
+impl RenameRule {
+  fn apply(self, ident: &syn::Ident) -> syn::Ident {
+    let name = ident.to_string();
+    let name = name.trim_start_matches("r#");
+    let renamed = match self {
+      RenameRule::CamelCase | RenameRule::PascalCase => {
+        let mut renamed = String::new();
+        let mut upper = matches!(self, RenameRule::PascalCase);
+        for c in name.chars() {
+          if c == '_' {
+            upper = !renamed.is_empty();
+          } else if upper {
+            renamed.extend(c.to_uppercase());
+            upper = false;
+          } else {
+            renamed.push(c);
+          }
+        }
+        renamed
+      }
+      RenameRule::ScreamingSnakeCase => name.to_uppercase(),
+    };
+    syn::Ident::new(&renamed, ident.span())
+  }
+}
+
+impl FieldReceiver {
+  fn validate(&self, style: ast::Style) -> Vec<darling::Error> {
+    let mut errors = vec![];
+    if style == ast::Style::Tuple
+      && !self.skip
+      && self.compute.is_none()
+      && self.rename.is_none()
+      && self.proto_name.is_none()
+    {
+      errors.push("fields of a tuple struct need `rename` or `proto_name` to name the proto field");
+    }
+    if self.rename.is_some() && self.proto_name.is_some() {
+      errors.push("`rename` and `proto_name` can not be used together");
+    }
+    if self.default.is_some() && !self.skip {
+      errors.push("`default` can only be used together with `skip`");
+    }
+    if self.with.is_some() && (self.map_fn.is_some() || self.skip) {
+      errors.push("`with` can not be used together with `map_fn` or `skip`");
+    }
+    if self.compute.is_some() {
+      if style == ast::Style::Tuple {
+        errors.push("`compute` can only be used on fields of a struct with named fields");
+      }
+      if self.rename.is_some()
+        || self.proto_name.is_some()
+        || self.map_fn.is_some()
+        || self.with.is_some()
+        || self.skip
+        || self.default.is_some()
+        || self.required
+        || self.optional
+        || self.or_default
+        || self.pack_default
+        || self.proto_enum
+        || self.none_on_unspecified
+        || self.require_nonzero
+        || self.flatten.is_some()
+        || self.from.is_some()
+      {
+        errors.push("`compute` can not be used together with other field attributes");
+      }
+    }
+    if self.from.is_some() && (self.map_fn.is_some() || self.skip || self.required || self.optional)
+    {
+      errors
+        .push("`from` can not be used together with `map_fn`, `skip`, `required` or `optional`");
+    }
+    if self.flatten.is_some() && self.skip {
+      errors.push("`flatten` can not be used together with `skip`");
+    }
+    if self.required && self.optional {
+      errors.push("`required` and `optional` can not be used together");
+    }
+    if self.or_default && (self.required || self.optional) {
+      errors.push("`or_default` can not be used together with `required` or `optional`");
+    }
+    if (self.required || self.optional)
+      && (self.map_fn.is_some() || self.with.is_some() || self.skip)
+    {
+      errors
+        .push("`required` and `optional` can not be used together with `map_fn`, `with` or `skip`");
+    }
+    if self.or_default
+      && (self.map_fn.is_some() || self.with.is_some() || self.skip || self.from.is_some())
+    {
+      errors.push("`or_default` can not be used together with `map_fn`, `with`, `skip` or `from`");
+    }
+    if self.pack_default
+      && (self.map_fn.is_some()
+        || self.with.is_some()
+        || self.skip
+        || self.from.is_some()
+        || self.required
+        || self.optional
+        || self.or_default
+        || self.require_nonzero)
+    {
+      errors.push(
+        "`pack_default` can not be used together with `map_fn`, `with`, `skip`, `from`, `required`, `optional`, `or_default` or `require_nonzero`",
+      );
+    }
+    if self.none_on_unspecified && !is_option(&self.ty) {
+      errors.push("`none_on_unspecified` can only be used on `Option` fields");
+    }
+    if self.none_on_unspecified
+      && (self.map_fn.is_some()
+        || self.with.is_some()
+        || self.skip
+        || self.from.is_some()
+        || self.required
+        || self.optional
+        || self.or_default
+        || self.pack_default
+        || self.require_nonzero)
+    {
+      errors
+        .push("`none_on_unspecified` can not be used together with other conversion attributes");
+    }
+    if self.require_nonzero
+      && (self.map_fn.is_some() || self.skip || self.required || self.optional || self.or_default)
+    {
+      errors.push(
+        "`require_nonzero` can not be used together with `map_fn`, `skip`, `required`, `optional` or `or_default`",
+      );
+    }
+    errors.into_iter().map(|msg| self.error(msg)).collect()
+  }
+
+  fn validate_transparent(&self) -> darling::Result<()> {
+    if self.rename.is_some()
+      || self.proto_name.is_some()
+      || self.map_fn.is_some()
+      || self.with.is_some()
+      || self.skip
+      || self.default.is_some()
+      || self.required
+      || self.optional
+      || self.or_default
+      || self.pack_default
+      || self.proto_enum
+      || self.none_on_unspecified
+      || self.require_nonzero
+      || self.flatten.is_some()
+      || self.compute.is_some()
+      || self.from.is_some()
+    {
+      return Err(
+        self.error("the field of a newtype struct without `message_type` can not have attributes"),
+      );
+    }
+    Ok(())
+  }
+
+  fn error(&self, msg: &str) -> darling::Error {
+    let error = darling::Error::custom(msg);
+    match self.ident {
+      Some(ref ident) => error.with_span(ident),
+      None => error.with_span(&self.ty),
+    }
+  }
+
+  /// `name` of a named field, the position of a tuple struct field
+  fn member(&self) -> syn::Member {
+    match self.ident {
+      Some(ref ident) => syn::Member::Named(ident.clone()),
+      None => syn::Member::Unnamed(self.index.into()),
+    }
+  }
+
+  /// `proto_field: expr,` of the packed message literal, reading from `value`
+  fn pack_tokens(&self, by_ref: bool) -> TokenStream {
+    let value_field_ident = self.proto_ident();
+    let packed = self.pack_expr(by_ref);
+    quote! {
+      #value_field_ident: #packed,
+    }
+  }
+
+  /// Packed value of the field, reading from `value`, errors point at the field
+  fn pack_expr(&self, by_ref: bool) -> TokenStream {
+    let field_ident = self.member();
+    let span = self.ty.span();
+    let at = quote! { .map_err(|err| err.at(stringify!(#field_ident)))? };
+    let field = if by_ref {
+      quote! { value.#field_ident.clone() }
+    } else {
+      quote_spanned! {span=> value.#field_ident }
+    };
+    if let Some(map_fn) = self.map_fn.as_ref() {
+      quote_spanned! {span=> #map_fn(#field) }
+    } else if let Some(from) = self.from.as_ref() {
+      let converted = quote_spanned! {span=> <#from as From<_>>::from(#field) };
+      if let Some(with) = self.with.as_ref() {
+        quote_spanned! {span=> #with::pack(#converted)#at }
+      } else {
+        quote_spanned! {span=> s2_grpc_utils::S2ProtoPack::pack(#converted)#at }
+      }
+    } else if let Some(with) = self.with.as_ref() {
+      quote_spanned! {span=> #with::pack(#field)#at }
+    } else if self.required || self.or_default {
+      if by_ref {
+        quote_spanned! {span=> Some(s2_grpc_utils::S2ProtoPackRef::pack_ref(&value.#field_ident)#at) }
+      } else {
+        quote_spanned! {span=> Some(s2_grpc_utils::S2ProtoPack::pack(value.#field_ident)#at) }
+      }
+    } else if self.none_on_unspecified {
+      if by_ref {
+        quote_spanned! {span=>
+          match &value.#field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoPackRef::<i32>::pack_ref(v)#at,
+            None => 0,
+          }
+        }
+      } else {
+        quote_spanned! {span=>
+          match value.#field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoPack::<i32>::pack(v)#at,
+            None => 0,
+          }
+        }
+      }
+    } else if self.pack_default {
+      if by_ref {
+        quote_spanned! {span=>
+          Some(match &value.#field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoPackRef::pack_ref(v)#at,
+            None => Default::default(),
+          })
+        }
+      } else {
+        quote_spanned! {span=>
+          Some(match value.#field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoPack::pack(v)#at,
+            None => Default::default(),
+          })
+        }
+      }
+    } else if self.optional {
+      if by_ref {
+        quote_spanned! {span=>
+          match &value.#field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoPackRef::pack_ref(v)#at,
+            None => Default::default(),
+          }
+        }
+      } else {
+        quote_spanned! {span=>
+          match value.#field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoPack::pack(v)#at,
+            None => Default::default(),
+          }
+        }
+      }
+    } else if by_ref {
+      quote_spanned! {span=> s2_grpc_utils::S2ProtoPackRef::pack_ref(&value.#field_ident)#at }
+    } else {
+      quote_spanned! {span=> s2_grpc_utils::S2ProtoPack::pack(value.#field_ident)#at }
+    }
+  }
+
+  /// `field: expr,` of the unpacked struct literal, reading from `value`
+  fn unpack_tokens(&self, by_ref: bool) -> TokenStream {
+    let field_ident = self.member();
+    let field_expr = if let Some(result) = self.unpack_result(by_ref) {
+      quote_spanned! {self.ty.span()=> #result? }
+    } else {
+      self.unpack_value(by_ref)
+    };
+    quote! {
+      #field_ident: #field_expr,
+    }
+  }
+
+  /// The unpack `Result` of the field with errors pointing at the field,
+  /// `None` if the field can not fail to unpack
+  fn unpack_result(&self, by_ref: bool) -> Option<TokenStream> {
+    if self.skip || self.map_fn.is_some() {
+      return None;
+    }
+    let field_ident = self.member();
+    let span = self.ty.span();
+    let ty = &self.ty;
+    let value_field_ident = self.proto_ident();
+    let source = self.source();
+    let unpack = if let Some(from) = self.from.as_ref() {
+      let unpack = if let Some(with) = self.with.as_ref() {
+        if by_ref {
+          quote_spanned! {span=> #with::unpack(#source.#value_field_ident.clone()) }
+        } else {
+          quote_spanned! {span=> #with::unpack(#source.#value_field_ident) }
+        }
+      } else if by_ref {
+        quote_spanned! {span=> <#from as s2_grpc_utils::S2ProtoUnpackRef<_>>::unpack_ref(&#source.#value_field_ident) }
+      } else {
+        quote_spanned! {span=> <#from as S2ProtoUnpack<_>>::unpack(#source.#value_field_ident) }
+      };
+      quote_spanned! {span=> #unpack.map(<#ty as From<#from>>::from) }
+    } else if let Some(with) = self.with.as_ref() {
+      if by_ref {
+        quote_spanned! {span=> #with::unpack(#source.#value_field_ident.clone()) }
+      } else {
+        quote_spanned! {span=> #with::unpack(#source.#value_field_ident) }
+      }
+    } else if self.required || self.or_default {
+      let none = if self.or_default {
+        quote_spanned! {span=> None => Ok(Default::default()) }
+      } else {
+        quote_spanned! {span=> None => Err(s2_grpc_utils::result::Error::ValueNotPresent) }
+      };
+      if by_ref {
+        quote_spanned! {span=>
+          match &#source.#value_field_ident {
+            Some(v) => s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(v),
+            #none,
+          }
+        }
+      } else {
+        quote_spanned! {span=>
+          match #source.#value_field_ident {
+            Some(v) => S2ProtoUnpack::unpack(v),
+            #none,
+          }
+        }
+      }
+    } else if self.none_on_unspecified {
+      if by_ref {
+        quote_spanned! {span=>
+          match &#source.#value_field_ident {
+            0 => Ok(None),
+            v => s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(v).map(Some),
+          }
+        }
+      } else {
+        quote_spanned! {span=>
+          match #source.#value_field_ident {
+            0 => Ok(None),
+            v => S2ProtoUnpack::unpack(v).map(Some),
+          }
+        }
+      }
+    } else if self.optional {
+      if by_ref {
+        quote_spanned! {span=> s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(&#source.#value_field_ident).map(Some) }
+      } else {
+        quote_spanned! {span=> S2ProtoUnpack::unpack(#source.#value_field_ident).map(Some) }
+      }
+    } else if by_ref {
+      quote_spanned! {span=> s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(&#source.#value_field_ident) }
+    } else {
+      quote_spanned! {span=> S2ProtoUnpack::unpack(#source.#value_field_ident) }
+    };
+    let unpack = quote_spanned! {span=>
+      #unpack.map_err(|err| {
+        if let s2_grpc_utils::result::Error::ValueNotPresent = err {
+          s2_grpc_utils::result::Error::FieldValueNotPresent {
+            field_name: stringify!(#field_ident),
+          }
+        } else {
+          err.at(stringify!(#field_ident))
+        }
+      })
+    };
+    // proto3 can not tell a zero scalar from an unset one
+    if self.require_nonzero {
+      Some(quote_spanned! {span=>
+        ({
+          fn is_default<T: Default + PartialEq>(v: &T) -> bool {
+            *v == T::default()
+          }
+          if is_default(&#source.#value_field_ident) {
+            Err(s2_grpc_utils::result::Error::FieldValueNotPresent {
+              field_name: stringify!(#value_field_ident),
+            })
+          } else {
+            #unpack
+          }
+        })
+      })
+    } else {
+      Some(unpack)
+    }
+  }
+
+  /// Value of a `skip` or `map_fn` field
+  fn unpack_value(&self, by_ref: bool) -> TokenStream {
+    let value_field_ident = self.proto_ident();
+    let source = self.source();
+    if self.skip {
+      if let Some(default) = self.default.as_ref() {
+        quote! { #default }
+      } else {
+        quote! { Default::default() }
+      }
+    } else {
+      let map_fn = self.map_fn.as_ref();
+      if by_ref {
+        quote! {
+          #map_fn(#source.#value_field_ident.clone())
+        }
+      } else {
+        quote! {
+          #map_fn(#source.#value_field_ident)
+        }
+      }
+    }
+  }
+
+  /// The message the field is read from when unpacking,
+  /// the sub-message bound by `flatten_bindings` for `flatten` fields
+  fn source(&self) -> TokenStream {
+    match self.flatten {
+      Some(ref group) => quote::format_ident!("__flatten_{}", group).into_token_stream(),
+      None => quote_spanned! {self.ty.span()=> value },
+    }
+  }
+
+  fn proto_ident(&self) -> &syn::Ident {
+    self
+      .proto_name
+      .as_ref()
+      .or_else(|| self.rename.as_ref())
+      .or_else(|| self.renamed.as_ref())
+      .unwrap_or_else(|| self.ident.as_ref().unwrap())
+  }
+}
+
+#[derive(Debug, FromVariant)]
+#[darling(attributes(s2_grpc))]
+struct VariantReceiver {
+  ident: syn::Ident,
+  fields: ast::Fields<syn::Type>,
+  #[darling(default)]
+  rename: Option<syn::Ident>,
+}
+
+impl VariantReceiver {
+  fn proto_ident(&self) -> &syn::Ident {
+    self.rename.as_ref().unwrap_or(&self.ident)
+  }
+
+  /// Field of the message set by the variant with `variant_fields`,
+  /// `rename` or the variant name in snake case
+  fn field_ident(&self) -> syn::Ident {
+    if let Some(rename) = self.rename.as_ref() {
+      return rename.clone();
+    }
+    let mut name = String::new();
+    for (i, c) in self.ident.to_string().chars().enumerate() {
+      if c.is_uppercase() {
+        if i > 0 {
+          name.push('_');
+        }
+        name.extend(c.to_lowercase());
+      } else {
+        name.push(c);
+      }
+    }
+    syn::Ident::new(&name, self.ident.span())
+  }
 }
@@ -9,6 +9,27 @@ pub struct InputReceiver {
   generics: syn::Generics,
   data: ast::Data<VariantReceiver, ()>,
   proto_enum_type: syn::Path,
+  #[darling(default)]
+  unknown: Option<syn::Ident>,
+}
+
+impl InputReceiver {
+  pub fn validate(&self) -> darling::Result<()> {
+    if let Some(ref unknown) = self.unknown {
+      let variants = self
+        .data
+        .as_ref()
+        .take_enum()
+        .expect("Should never be struct");
+      if !variants.iter().any(|v| v.ident == *unknown) {
+        return Err(
+          darling::Error::custom(format!("`unknown` variant `{}` is not found", unknown))
+            .with_span(unknown),
+        );
+      }
+    }
+    Ok(())
+  }
 }
 
 impl ToTokens for InputReceiver {
@@ -18,6 +39,7 @@ impl ToTokens for InputReceiver {
       ref generics,
       ref data,
       ref proto_enum_type,
+      ref unknown,
     } = *self;
 
     let (imp, ty, wher) = generics.split_for_impl();
@@ -56,6 +78,12 @@ impl ToTokens for InputReceiver {
 
     let name = ident.to_string();
 
+    // Discriminants unknown to the proto enum or without a variant map to `unknown` if set
+    let fallback = match unknown {
+      Some(unknown) => quote! { .or(Some(Self::#unknown)) },
+      None => quote! {},
+    };
+
     tokens.extend(quote! {
       impl #imp s2_grpc_utils::S2ProtoEnum for #ident #ty  #wher {
         type ProtoEnum = #proto_enum_type;
@@ -69,6 +97,7 @@ impl ToTokens for InputReceiver {
                 _ => None,
               }
             })
+            #fallback
         }
 
         fn pack(&self) -> #proto_enum_type {
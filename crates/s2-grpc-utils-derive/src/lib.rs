@@ -19,14 +19,23 @@ macro_rules! try_parse {
 #[proc_macro_derive(S2ProtoPack, attributes(s2_grpc))]
 pub fn derive_pack(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
-  let receiver = try_parse!(derive_s2_proto::InputReceiver::from_derive_input(&input));
+  let receiver = try_parse!(derive_s2_proto::InputReceiver::from_derive_input(&input))
+    .infer_message_type()
+    .index_fields()
+    .rename_fields();
+  try_parse!(receiver.validate());
   TokenStream::from(quote!(#receiver))
 }
 
 #[proc_macro_derive(S2ProtoUnpack, attributes(s2_grpc))]
 pub fn derive_unpack(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
-  let receiver = try_parse!(derive_s2_proto::InputReceiver::from_derive_input(&input)).to_unpack();
+  let receiver = try_parse!(derive_s2_proto::InputReceiver::from_derive_input(&input))
+    .infer_message_type()
+    .index_fields()
+    .rename_fields()
+    .to_unpack();
+  try_parse!(receiver.validate());
   TokenStream::from(quote!(#receiver))
 }
 
@@ -36,5 +45,6 @@ pub fn derive_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
   let receiver = try_parse!(derive_s2_proto_enum::InputReceiver::from_derive_input(
     &input
   ));
+  try_parse!(receiver.validate());
   TokenStream::from(quote!(#receiver))
 }
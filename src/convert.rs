@@ -1,37 +1,139 @@
+#[cfg(feature = "bigdecimal")]
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Utc};
-use prost_types;
-use prost_types::{Timestamp, Value};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use prost_types::value::Kind;
+use prost_types::{Any, ListValue, Struct, Timestamp, Value};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use snafu::ResultExt;
+use std::borrow::Cow;
 use std::cmp::Eq;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::hash::Hash;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::result::{self, Result};
-use crate::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
+use crate::{S2ProtoEnum, S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
 
+/// Implements conversions of `$rust` from/to `Option<$proto>`, a missing value
+/// fails to unpack with `ValueNotPresent`. The generic form takes the type
+/// parameters in brackets and requires the matching conversion of `$rust` itself
+///
+/// This can't be a blanket `impl<T: S2ProtoPack<P>, P> S2ProtoPack<Option<P>> for T`: with
+/// `T = Option<T2>` it overlaps with the `Option<T2> => Option<T1>` impl in lib.rs, since a
+/// downstream crate may implement `S2ProtoPack<T1>` for `Option<T2>` of its own types.
+/// Every type that converts from/to `Option<P>` has to invoke this macro
+#[doc(hidden)]
+#[macro_export]
 macro_rules! impl_option {
+  ([$($param:ident),+] $rust:ty => $proto:ty) => {
+    impl<$($param),+> $crate::S2ProtoPack<Option<$proto>> for $rust
+    where
+      $rust: $crate::S2ProtoPack<$proto>,
+    {
+      fn pack(self) -> $crate::result::Result<Option<$proto>> {
+        Ok(Some($crate::S2ProtoPack::<$proto>::pack(self)?))
+      }
+    }
+
+    impl<$($param),+> $crate::S2ProtoPackRef<Option<$proto>> for $rust
+    where
+      $rust: $crate::S2ProtoPackRef<$proto>,
+    {
+      fn pack_ref(&self) -> $crate::result::Result<Option<$proto>> {
+        Ok(Some($crate::S2ProtoPackRef::<$proto>::pack_ref(self)?))
+      }
+    }
+
+    impl<$($param),+> $crate::S2ProtoUnpack<Option<$proto>> for $rust
+    where
+      $rust: $crate::S2ProtoUnpack<$proto>,
+    {
+      fn unpack(value: Option<$proto>) -> $crate::result::Result<$rust> {
+        if let Some(value) = value {
+          <$rust as $crate::S2ProtoUnpack<$proto>>::unpack(value)
+        } else {
+          Err($crate::result::Error::ValueNotPresent)
+        }
+      }
+    }
+
+    impl<$($param),+> $crate::S2ProtoUnpackRef<Option<$proto>> for $rust
+    where
+      $rust: $crate::S2ProtoUnpackRef<$proto>,
+    {
+      fn unpack_ref(value: &Option<$proto>) -> $crate::result::Result<$rust> {
+        if let Some(value) = value {
+          <$rust as $crate::S2ProtoUnpackRef<$proto>>::unpack_ref(value)
+        } else {
+          Err($crate::result::Error::ValueNotPresent)
+        }
+      }
+    }
+  };
+  // Packing only, for types whose generic unpack impl would overlap with `Option<$proto>`
+  (@pack $rust:ty => $proto:ty) => {
+    impl $crate::S2ProtoPack<Option<$proto>> for $rust {
+      fn pack(self) -> $crate::result::Result<Option<$proto>> {
+        Ok(Some($crate::S2ProtoPack::<$proto>::pack(self)?))
+      }
+    }
+
+    impl $crate::S2ProtoPackRef<Option<$proto>> for $rust {
+      fn pack_ref(&self) -> $crate::result::Result<Option<$proto>> {
+        Ok(Some($crate::S2ProtoPackRef::<$proto>::pack_ref(self)?))
+      }
+    }
+  };
   ($rust:ty => $proto:ty) => {
-    impl S2ProtoPack<Option<$proto>> for $rust {
-      fn pack(self) -> Result<Option<$proto>> {
-        Ok(Some(self.pack()?))
+    $crate::impl_option!(@pack $rust => $proto);
+
+    impl $crate::S2ProtoUnpack<Option<$proto>> for $rust {
+      fn unpack(value: Option<$proto>) -> $crate::result::Result<$rust> {
+        if let Some(value) = value {
+          <$rust as $crate::S2ProtoUnpack<$proto>>::unpack(value)
+        } else {
+          Err($crate::result::Error::ValueNotPresent)
+        }
       }
     }
 
-    impl S2ProtoUnpack<Option<$proto>> for $rust {
-      fn unpack(value: Option<$proto>) -> Result<$rust> {
+    impl $crate::S2ProtoUnpackRef<Option<$proto>> for $rust {
+      fn unpack_ref(value: &Option<$proto>) -> $crate::result::Result<$rust> {
         if let Some(value) = value {
-          Ok(<$rust>::unpack(value)?)
+          <$rust as $crate::S2ProtoUnpackRef<$proto>>::unpack_ref(value)
         } else {
-          Err(result::Error::ValueNotPresent)
+          Err($crate::result::Error::ValueNotPresent)
         }
       }
     }
   };
 }
 
+/// Implements `S2ProtoPackRef` and `S2ProtoUnpackRef` by cloning,
+/// for types whose conversion needs ownership
+macro_rules! impl_ref_by_clone {
+  (
+    $($rust:ty => $proto:ty),*
+  ) => {
+    $(
+      impl S2ProtoPackRef<$proto> for $rust {
+        fn pack_ref(&self) -> Result<$proto> {
+          self.clone().pack()
+        }
+      }
+
+      impl S2ProtoUnpackRef<$proto> for $rust {
+        fn unpack_ref(value: &$proto) -> Result<$rust> {
+          <$rust>::unpack(value.clone())
+        }
+      }
+    )*
+  }
+}
+
 // JSON value
 
 impl S2ProtoPack<Value> for JsonValue {
@@ -48,11 +150,59 @@ impl S2ProtoUnpack<Value> for JsonValue {
 
 impl_option!(JsonValue => Value);
 
+impl S2ProtoPack<Struct> for JsonValue {
+  fn pack(self) -> Result<Struct> {
+    if !self.is_object() {
+      return Err(result::Error::JsonValueNotObject);
+    }
+    match json_value_to_value(self)?.kind {
+      Some(Kind::StructValue(v)) => Ok(v),
+      _ => unreachable!(),
+    }
+  }
+}
+
+impl S2ProtoUnpack<Struct> for JsonValue {
+  fn unpack(value: Struct) -> Result<JsonValue> {
+    value_to_json_value(Value {
+      kind: Some(Kind::StructValue(value)),
+    })
+  }
+}
+
+impl S2ProtoPack<ListValue> for JsonValue {
+  fn pack(self) -> Result<ListValue> {
+    if !self.is_array() {
+      return Err(result::Error::JsonValueNotArray);
+    }
+    match json_value_to_value(self)?.kind {
+      Some(Kind::ListValue(v)) => Ok(v),
+      _ => unreachable!(),
+    }
+  }
+}
+
+impl S2ProtoUnpack<ListValue> for JsonValue {
+  fn unpack(value: ListValue) -> Result<JsonValue> {
+    value_to_json_value(Value {
+      kind: Some(Kind::ListValue(value)),
+    })
+  }
+}
+
+impl_ref_by_clone! {
+  JsonValue => Value,
+  JsonValue => Struct,
+  JsonValue => ListValue
+}
+
+impl_option!(JsonValue => Struct);
+impl_option!(JsonValue => ListValue);
+
 const MAX_JSON_NEST: usize = 100;
 
 fn value_to_json_value(value: Value) -> Result<JsonValue> {
   fn convert(nest: usize, value: Value) -> Result<JsonValue> {
-    use prost_types::{value::Kind, ListValue, Struct};
     use serde_json::{Map as JsonMap, Number as JsonNumber};
 
     if nest >= MAX_JSON_NEST {
@@ -97,7 +247,6 @@ fn value_to_json_value(value: Value) -> Result<JsonValue> {
 
 fn json_value_to_value(value: JsonValue) -> Result<Value> {
   fn convert(nest: usize, value: JsonValue) -> Result<Value> {
-    use prost_types::{value::Kind, ListValue, Struct};
     use std::collections::BTreeMap;
 
     if nest >= MAX_JSON_NEST {
@@ -139,17 +288,27 @@ fn json_value_to_value(value: JsonValue) -> Result<Value> {
 }
 
 /// Helper type to convert any serializable type from/to `google.protobuf.Value`
+/// or `google.protobuf.Any` with `JSON_TYPE_URL`
 pub struct Json<T>(pub T);
 
 impl<T> S2ProtoPack<Value> for Json<T>
 where
-  T: Serialize + for<'de> Deserialize<'de>,
+  T: Serialize,
 {
   fn pack(self) -> Result<Value> {
     pack_value(self.0)
   }
 }
 
+impl<T> S2ProtoPackRef<Value> for Json<T>
+where
+  T: Serialize,
+{
+  fn pack_ref(&self) -> Result<Value> {
+    pack_value(&self.0)
+  }
+}
+
 impl<T> S2ProtoUnpack<Value> for Json<T>
 where
   T: Serialize + for<'de> Deserialize<'de>,
@@ -163,7 +322,11 @@ pub fn pack_value<T>(value: T) -> Result<Value>
 where
   T: Serialize,
 {
-  serde_json::to_value(&value).context(result::Json)?.pack()
+  #[cfg(feature = "strict_floats")]
+  crate::strict_floats::check(&value)?;
+  serde_json::to_value(&value)
+    .context(result::JsonEncode)?
+    .pack()
 }
 
 pub fn unpack_value<T>(value: Value) -> Result<T>
@@ -171,214 +334,2813 @@ where
   T: for<'de> Deserialize<'de>,
 {
   let value = JsonValue::unpack(value)?;
-  Ok(serde_json::from_value(value).context(result::Json)?)
+  serde_json::from_value(value).context(result::Json)
 }
 
-// Timestamp
+// Nullable values: `None` is `google.protobuf.Value` null, so `Option<Option<T>>` tells an
+// unset `Value` field from an explicit null
 
-impl S2ProtoPack<Timestamp> for DateTime<Utc> {
-  fn pack(self) -> Result<Timestamp> {
-    Ok(Timestamp {
-      seconds: self.timestamp(),
-      nanos: self.timestamp_subsec_nanos() as i32,
-    })
+impl<T> S2ProtoPack<Value> for Option<T>
+where
+  T: S2ProtoPack<Value>,
+{
+  fn pack(self) -> Result<Value> {
+    match self {
+      Some(value) => value.pack(),
+      None => Ok(null_value()),
+    }
   }
 }
 
-impl S2ProtoUnpack<Timestamp> for DateTime<Utc> {
-  fn unpack(Timestamp { seconds, nanos }: Timestamp) -> Result<DateTime<Utc>> {
-    let dt = chrono::NaiveDateTime::from_timestamp(seconds, nanos as u32);
-    Ok(DateTime::from_utc(dt, Utc))
+impl<T> S2ProtoPackRef<Value> for Option<T>
+where
+  T: S2ProtoPackRef<Value>,
+{
+  fn pack_ref(&self) -> Result<Value> {
+    match self {
+      Some(value) => value.pack_ref(),
+      None => Ok(null_value()),
+    }
   }
 }
 
-// Duration
-
-impl S2ProtoPack<prost_types::Duration> for chrono::Duration {
-  fn pack(self) -> Result<prost_types::Duration> {
-    let duration =
-      <prost_types::Duration as From<std::time::Duration>>::from(self.to_std().map_err(|e| {
-        result::Error::ParseDuration {
-          message: e.to_string(),
-        }
-      })?);
-
-    Ok(duration)
+impl<T> S2ProtoUnpack<Value> for Option<T>
+where
+  T: S2ProtoUnpack<Value>,
+{
+  fn unpack(value: Value) -> Result<Option<T>> {
+    match value.kind {
+      None | Some(Kind::NullValue(_)) => Ok(None),
+      Some(_) => T::unpack(value).map(Some),
+    }
   }
 }
 
-impl S2ProtoUnpack<prost_types::Duration> for chrono::Duration {
-  fn unpack(value: prost_types::Duration) -> Result<chrono::Duration> {
-    use std::convert::TryInto;
-
-    let std_duration = <prost_types::Duration as TryInto<std::time::Duration>>::try_into(value)
-      .map_err(|e| result::Error::ParseDuration {
-        message: format!(
-          "Source duration value is out of range for the target type {}",
-          e.as_secs().to_string()
-        ),
-      })?;
+impl<T> S2ProtoUnpackRef<Value> for Option<T>
+where
+  T: S2ProtoUnpackRef<Value>,
+{
+  fn unpack_ref(value: &Value) -> Result<Option<T>> {
+    match value.kind {
+      None | Some(Kind::NullValue(_)) => Ok(None),
+      Some(_) => T::unpack_ref(value).map(Some),
+    }
+  }
+}
 
-    chrono::Duration::from_std(std_duration).map_err(|e| result::Error::ParseDuration {
-      message: e.to_string(),
-    })
+fn null_value() -> Value {
+  Value {
+    kind: Some(Kind::NullValue(prost_types::NullValue::NullValue as i32)),
   }
 }
 
-impl_option!(DateTime<Utc> => Timestamp);
-impl_option!(chrono::Duration => prost_types::Duration);
+// JSON in Any
 
-// BigDecimal
+/// Type URL of `google.protobuf.Any` values holding JSON bytes
+pub const JSON_TYPE_URL: &str = "s2/json";
 
-impl S2ProtoPack<String> for BigDecimal {
-  fn pack(self) -> Result<String> {
-    Ok(self.to_string())
+impl<T> S2ProtoUnpackRef<Value> for Json<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  fn unpack_ref(value: &Value) -> Result<Json<T>> {
+    unpack_value(value.clone()).map(Json)
   }
 }
 
-impl<T> S2ProtoUnpack<T> for BigDecimal
+// `Json<T>` in `Any` is `Encoded<T, JsonFormat>`, only the `Value` impls above are its own
+
+/// Only needs `T: Serialize`, so `Json(&value)` packs a borrowed value without cloning it
+impl<T> S2ProtoPack<Any> for Json<T>
 where
-  T: AsRef<str>,
+  T: Serialize,
 {
-  fn unpack(v: T) -> Result<BigDecimal> {
-    v.as_ref().parse().context(result::ParseBigDecimal)
+  fn pack(self) -> Result<Any> {
+    Encoded::<T, JsonFormat>::new(self.0).pack()
   }
 }
 
-// Wrappers
+impl<T> S2ProtoPackRef<Any> for Json<T>
+where
+  T: Serialize,
+{
+  fn pack_ref(&self) -> Result<Any> {
+    Encoded::<&T, JsonFormat>::new(&self.0).pack()
+  }
+}
 
-macro_rules! impl_self {
-  (
-    $($ty:ty),*
-  ) => {
-    $(
-      impl S2ProtoPack<$ty> for $ty {
-        fn pack(self) -> Result<$ty> {
-          Ok(self)
-        }
-      }
+impl<T> S2ProtoUnpack<Any> for Json<T>
+where
+  T: Serialize + for<'de> Deserialize<'de>,
+{
+  fn unpack(value: Any) -> Result<Json<T>> {
+    Self::unpack_ref(&value)
+  }
+}
 
-      impl S2ProtoUnpack<$ty> for $ty {
-        fn unpack(value: $ty) -> Result<$ty> {
-          Ok(value)
-        }
+impl<T> S2ProtoUnpackRef<Any> for Json<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  /// Keeps returning `JsonTypeUrlUnknown` for another `type_url`, like `unpack_any`
+  fn unpack_ref(value: &Any) -> Result<Json<T>> {
+    match Encoded::<T, JsonFormat>::unpack_ref(value) {
+      Ok(value) => Ok(Json(value.into_inner())),
+      Err(result::Error::AnyTypeUrlMismatch { type_url, .. }) => {
+        Err(result::Error::JsonTypeUrlUnknown { type_url })
       }
-    )*
+      Err(err) => Err(err),
+    }
   }
 }
 
-impl_self! {
-  f32,
-  f64,
-  i64,
-  u64,
-  i32,
-  u32,
-  bool,
-  String
+impl_option!([T] Json<T> => Value);
+impl_option!([T] Json<T> => Any);
+
+/// Each value of the object is packed like `Json<serde_json::Value>` into `Any`
+impl S2ProtoPack<HashMap<String, Any>> for serde_json::Map<String, JsonValue> {
+  fn pack(self) -> Result<HashMap<String, Any>> {
+    self.pack_ref()
+  }
 }
 
-// repeated value
+impl S2ProtoPackRef<HashMap<String, Any>> for serde_json::Map<String, JsonValue> {
+  fn pack_ref(&self) -> Result<HashMap<String, Any>> {
+    let mut r = HashMap::with_capacity(self.len());
+    for (k, v) in self {
+      let v2 = pack_any(v).map_err(|e| result::Error::MapEntry {
+        source: Box::new(e),
+      })?;
+      r.insert(k.clone(), v2);
+    }
+    Ok(r)
+  }
+}
 
-impl<T, T2> S2ProtoPack<Vec<T>> for Vec<T2>
-where
-  T2: S2ProtoPack<T>,
-{
-  fn pack(self) -> Result<Vec<T>> {
-    let mut r = vec![];
-    for (i, elem) in self.into_iter().enumerate() {
-      let item = elem.pack().map_err(|e| result::Error::ListElement {
+impl S2ProtoUnpack<HashMap<String, Any>> for serde_json::Map<String, JsonValue> {
+  fn unpack(value: HashMap<String, Any>) -> Result<serde_json::Map<String, JsonValue>> {
+    let mut r = serde_json::Map::with_capacity(value.len());
+    for (k, v) in value {
+      let v2 = unpack_any(v).map_err(|e| result::Error::MapEntry {
         source: Box::new(e),
-        index: i,
       })?;
-      r.push(item);
+      r.insert(k, v2);
     }
     Ok(r)
   }
 }
 
-impl<T, T2> S2ProtoUnpack<Vec<T>> for Vec<T2>
-where
-  T2: S2ProtoUnpack<T>,
-{
-  fn unpack(value: Vec<T>) -> Result<Vec<T2>> {
-    let mut r = vec![];
-    for (i, elem) in value.into_iter().enumerate() {
-      let item = T2::unpack(elem).map_err(|e| result::Error::ListElement {
+impl S2ProtoUnpackRef<HashMap<String, Any>> for serde_json::Map<String, JsonValue> {
+  fn unpack_ref(value: &HashMap<String, Any>) -> Result<serde_json::Map<String, JsonValue>> {
+    let mut r = serde_json::Map::with_capacity(value.len());
+    for (k, v) in value {
+      let v2 = unpack_any_ref(v, &[JSON_TYPE_URL]).map_err(|e| result::Error::MapEntry {
         source: Box::new(e),
-        index: i,
       })?;
-      r.push(item);
+      r.insert(k.clone(), v2);
     }
     Ok(r)
   }
 }
 
-// map
+impl_option!(serde_json::Map<String, JsonValue> => HashMap<String, Any>);
 
-impl<K, V, K2, V2> S2ProtoPack<HashMap<K, V>> for HashMap<K2, V2>
+/// Provides a per-type URL for `JsonWithUrl<T>`
+pub trait JsonTypeUrl {
+  fn type_url() -> &'static str;
+}
+
+/// Same as `Json<T>` but uses `T::type_url()` instead of `JSON_TYPE_URL`
+pub struct JsonWithUrl<T>(pub T);
+
+impl<T> S2ProtoPack<Any> for JsonWithUrl<T>
 where
-  K: Eq + Hash,
-  K2: S2ProtoPack<K> + Eq + Hash,
-  V2: S2ProtoPack<V>,
+  T: JsonTypeUrl + Serialize,
 {
-  fn pack(self) -> Result<HashMap<K, V>> {
-    let mut r = vec![];
-    for (k, v) in self.into_iter() {
-      let k2 = k.pack().map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      let v2 = v.pack().map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      r.push((k2, v2));
-    }
-    Ok(r.into_iter().collect())
+  fn pack(self) -> Result<Any> {
+    pack_any_with_url(self.0, T::type_url())
   }
 }
 
-impl<K, V, K2, V2> S2ProtoUnpack<HashMap<K, V>> for HashMap<K2, V2>
+impl<T> S2ProtoPackRef<Any> for JsonWithUrl<T>
 where
-  K: Eq + Hash,
-  K2: S2ProtoUnpack<K> + Eq + Hash,
-  V2: S2ProtoUnpack<V>,
+  T: JsonTypeUrl + Serialize,
 {
-  fn unpack(value: HashMap<K, V>) -> Result<HashMap<K2, V2>> {
-    let mut r = vec![];
-    for (k, v) in value.into_iter() {
-      let k2 = K2::unpack(k).map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      let v2 = V2::unpack(v).map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      r.push((k2, v2));
-    }
-    Ok(r.into_iter().collect())
+  fn pack_ref(&self) -> Result<Any> {
+    pack_any_with_url(&self.0, T::type_url())
   }
 }
 
-// Enum
+impl<T> S2ProtoUnpack<Any> for JsonWithUrl<T>
+where
+  T: JsonTypeUrl + for<'de> Deserialize<'de>,
+{
+  fn unpack(value: Any) -> Result<JsonWithUrl<T>> {
+    unpack_any_with_url(value, T::type_url()).map(JsonWithUrl)
+  }
+}
 
-impl<T> S2ProtoPack<i32> for T
+impl<T> S2ProtoUnpackRef<Any> for JsonWithUrl<T>
 where
-  T: S2ProtoEnum,
-  <T as S2ProtoEnum>::ProtoEnum: Into<i32>,
+  T: JsonTypeUrl + for<'de> Deserialize<'de>,
 {
-  fn pack(self) -> Result<i32> {
-    let v = <Self as S2ProtoEnum>::pack(&self);
-    Ok(v.into())
+  fn unpack_ref(value: &Any) -> Result<JsonWithUrl<T>> {
+    unpack_any_ref(value, &[T::type_url()]).map(JsonWithUrl)
   }
 }
 
-impl<T> S2ProtoUnpack<i32> for T
+impl_option!([T] JsonWithUrl<T> => Any);
+
+pub fn pack_any<T>(value: T) -> Result<Any>
 where
-  T: S2ProtoEnum,
+  T: Serialize,
 {
-  fn unpack(v: i32) -> Result<T> {
-    <Self as S2ProtoEnum>::from_i32(v).ok_or_else(|| result::Error::EnumDiscriminantNotFound {
-      enum_name: T::NAME,
-      discriminant: v,
-    })
+  pack_any_with_url(value, JSON_TYPE_URL)
+}
+
+/// Same as `pack_any`, but serializes into `buf` first, so a batch reusing `buf` doesn't
+/// regrow a buffer per value. `Any` owns its bytes, they are copied out with an exact capacity
+pub fn pack_any_into<T>(value: &T, buf: &mut Vec<u8>) -> Result<Any>
+where
+  T: Serialize,
+{
+  #[cfg(feature = "strict_floats")]
+  crate::strict_floats::check(value)?;
+  buf.clear();
+  serde_json::to_writer(&mut *buf, value).context(result::JsonEncode)?;
+  Ok(Any {
+    type_url: JSON_TYPE_URL.to_string(),
+    value: buf.as_slice().to_vec(),
+  })
+}
+
+pub fn unpack_any<T>(value: Any) -> Result<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  unpack_any_with_url(value, JSON_TYPE_URL)
+}
+
+/// Serializes `value` straight to JSON bytes, no intermediate `serde_json::Value` is built
+pub fn pack_any_with_url<T>(value: T, type_url: &str) -> Result<Any>
+where
+  T: Serialize,
+{
+  #[cfg(feature = "strict_floats")]
+  crate::strict_floats::check(&value)?;
+  Ok(Any {
+    type_url: type_url.to_string(),
+    value: serde_json::to_vec(&value).context(result::JsonEncode)?,
+  })
+}
+
+pub fn unpack_any_with_url<T>(value: Any, type_url: &str) -> Result<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  unpack_any_accepting(value, &[type_url])
+}
+
+/// Unpacks an `Any` whose `type_url` is one of `type_urls`,
+/// useful to keep accepting legacy URLs while migrating to a new one
+pub fn unpack_any_accepting<T>(value: Any, type_urls: &[&str]) -> Result<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  unpack_any_ref(&value, type_urls)
+}
+
+/// Same as `unpack_any`, but returns `DepthLimitExceeded` without deserializing if objects and
+/// arrays in the payload are nested deeper than `max_depth`, for `Any` from untrusted sources
+pub fn unpack_any_limited<T>(value: Any, max_depth: usize) -> Result<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  if json_depth_exceeds(&value.value, max_depth) {
+    return Err(result::Error::DepthLimitExceeded { limit: max_depth });
+  }
+  unpack_any(value)
+}
+
+/// Scans the JSON text for brackets outside of strings, the payload is not validated
+fn json_depth_exceeds(bytes: &[u8], max_depth: usize) -> bool {
+  let mut depth = 0_usize;
+  let mut in_string = false;
+  let mut escaped = false;
+  for &b in bytes {
+    if in_string {
+      match b {
+        _ if escaped => escaped = false,
+        b'\\' => escaped = true,
+        b'"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match b {
+      b'"' => in_string = true,
+      b'[' | b'{' => {
+        depth += 1;
+        if depth > max_depth {
+          return true;
+        }
+      }
+      b']' | b'}' => depth = depth.saturating_sub(1),
+      _ => {}
+    }
+  }
+  false
+}
+
+fn unpack_any_ref<T>(value: &Any, type_urls: &[&str]) -> Result<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  if !type_urls.contains(&value.type_url.as_str()) {
+    return Err(result::Error::JsonTypeUrlUnknown {
+      type_url: value.type_url.clone(),
+    });
+  }
+  serde_json::from_slice(&value.value).context(result::Json)
+}
+
+impl<T> Json<T>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  pub fn unpack_any_accepting(value: Any, type_urls: &[&str]) -> Result<Json<T>> {
+    unpack_any_accepting(value, type_urls).map(Json)
+  }
+}
+
+/// Raw JSON bytes in `Any`, whatever type they were serialized from
+impl Json<JsonValue> {
+  /// Wraps already serialized JSON, e.g. from a cache, in an `Any` with `JSON_TYPE_URL`.
+  /// The bytes are not checked to be valid JSON
+  pub fn from_raw_bytes(bytes: Vec<u8>) -> Any {
+    Any {
+      type_url: JSON_TYPE_URL.to_string(),
+      value: bytes,
+    }
+  }
+
+  /// The JSON bytes of an `Any` with `JSON_TYPE_URL`, without deserializing them
+  pub fn into_raw_bytes(value: Any) -> Result<Vec<u8>> {
+    if value.type_url != JSON_TYPE_URL {
+      return Err(result::Error::JsonTypeUrlUnknown {
+        type_url: value.type_url,
+      });
+    }
+    Ok(value.value)
+  }
+}
+
+// Encoded in Any
+
+/// A serde format for `Encoded<T, F>`, identified in `Any` by its `type_url`
+pub trait Format {
+  fn type_url() -> &'static str;
+  fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+  where
+    T: Serialize + ?Sized;
+  fn from_slice<T>(bytes: &[u8]) -> Result<T>
+  where
+    T: for<'de> Deserialize<'de>;
+}
+
+/// JSON with `JSON_TYPE_URL`, the same encoding `Json<T>` uses in `Any`
+pub struct JsonFormat;
+
+impl Format for JsonFormat {
+  fn type_url() -> &'static str {
+    JSON_TYPE_URL
+  }
+
+  fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+  where
+    T: Serialize + ?Sized,
+  {
+    #[cfg(feature = "strict_floats")]
+    crate::strict_floats::check(value)?;
+    serde_json::to_vec(value).context(result::JsonEncode)
+  }
+
+  fn from_slice<T>(bytes: &[u8]) -> Result<T>
+  where
+    T: for<'de> Deserialize<'de>,
+  {
+    serde_json::from_slice(bytes).context(result::Json)
+  }
+}
+
+/// JSON with `JSON_TYPE_URL` and object keys sorted at every level, so equal values
+/// always encode to the same bytes regardless of field declaration or map iteration order
+pub struct CanonicalJsonFormat;
+
+impl Format for CanonicalJsonFormat {
+  fn type_url() -> &'static str {
+    JSON_TYPE_URL
+  }
+
+  fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+  where
+    T: Serialize + ?Sized,
+  {
+    #[cfg(feature = "strict_floats")]
+    crate::strict_floats::check(value)?;
+    let value = serde_json::to_value(value).context(result::JsonEncode)?;
+    serde_json::to_vec(&sort_keys(value)).context(result::JsonEncode)
+  }
+
+  fn from_slice<T>(bytes: &[u8]) -> Result<T>
+  where
+    T: for<'de> Deserialize<'de>,
+  {
+    JsonFormat::from_slice(bytes)
+  }
+}
+
+/// Rebuilds objects in key order, `serde_json::Map` keeps insertion order
+/// if any crate in the build enables serde_json's `preserve_order`
+fn sort_keys(value: JsonValue) -> JsonValue {
+  match value {
+    JsonValue::Object(map) => {
+      let mut entries: Vec<_> = map.into_iter().collect();
+      entries.sort_by(|a, b| a.0.cmp(&b.0));
+      JsonValue::Object(
+        entries
+          .into_iter()
+          .map(|(k, v)| (k, sort_keys(v)))
+          .collect(),
+      )
+    }
+    JsonValue::Array(values) => JsonValue::Array(values.into_iter().map(sort_keys).collect()),
+    value => value,
+  }
+}
+
+#[cfg(feature = "cbor")]
+pub struct CborFormat;
+
+#[cfg(feature = "cbor")]
+impl Format for CborFormat {
+  fn type_url() -> &'static str {
+    "s2/cbor"
+  }
+
+  fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+  where
+    T: Serialize + ?Sized,
+  {
+    let mut bytes = vec![];
+    ciborium::ser::into_writer(value, &mut bytes).context(result::CborEncode)?;
+    Ok(bytes)
+  }
+
+  fn from_slice<T>(bytes: &[u8]) -> Result<T>
+  where
+    T: for<'de> Deserialize<'de>,
+  {
+    ciborium::de::from_reader(bytes).context(result::CborDecode)
+  }
+}
+
+/// Structs are encoded as maps, so fields can be added or reordered
+#[cfg(feature = "msgpack")]
+pub struct MsgpackFormat;
+
+#[cfg(feature = "msgpack")]
+impl Format for MsgpackFormat {
+  fn type_url() -> &'static str {
+    "s2/msgpack"
+  }
+
+  fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+  where
+    T: Serialize + ?Sized,
+  {
+    rmp_serde::to_vec_named(value).context(result::MsgpackEncode)
+  }
+
+  fn from_slice<T>(bytes: &[u8]) -> Result<T>
+  where
+    T: for<'de> Deserialize<'de>,
+  {
+    rmp_serde::from_slice(bytes).context(result::MsgpackDecode)
+  }
+}
+
+/// Helper type to convert any serializable type from/to `google.protobuf.Any`
+/// encoded with the format `F`
+pub struct Encoded<T, F = JsonFormat>(pub T, std::marker::PhantomData<F>);
+
+impl<T, F> Encoded<T, F> {
+  pub fn new(value: T) -> Self {
+    Encoded(value, std::marker::PhantomData)
+  }
+
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T, F> S2ProtoPack<Any> for Encoded<T, F>
+where
+  T: Serialize,
+  F: Format,
+{
+  fn pack(self) -> Result<Any> {
+    self.pack_ref()
+  }
+}
+
+impl<T, F> S2ProtoPackRef<Any> for Encoded<T, F>
+where
+  T: Serialize,
+  F: Format,
+{
+  fn pack_ref(&self) -> Result<Any> {
+    Ok(Any {
+      type_url: F::type_url().to_string(),
+      value: F::to_vec(&self.0)?,
+    })
+  }
+}
+
+impl<T, F> S2ProtoUnpack<Any> for Encoded<T, F>
+where
+  T: for<'de> Deserialize<'de>,
+  F: Format,
+{
+  fn unpack(value: Any) -> Result<Encoded<T, F>> {
+    Self::unpack_ref(&value)
+  }
+}
+
+impl<T, F> S2ProtoUnpackRef<Any> for Encoded<T, F>
+where
+  T: for<'de> Deserialize<'de>,
+  F: Format,
+{
+  fn unpack_ref(value: &Any) -> Result<Encoded<T, F>> {
+    if value.type_url != F::type_url() {
+      return Err(result::Error::AnyTypeUrlMismatch {
+        type_url: value.type_url.clone(),
+        expected: F::type_url().to_string(),
+      });
+    }
+    F::from_slice(&value.value).map(Encoded::new)
+  }
+}
+
+impl_option!([T, F] Encoded<T, F> => Any);
+
+/// `Encoded<T>` with sorted object keys, for content addressing or signing `Any` payloads
+pub type CanonicalJson<T> = Encoded<T, CanonicalJsonFormat>;
+
+// Proto in Any
+
+/// Fully qualified protobuf name of a prost message, the counterpart of `prost::Name`
+/// in newer prost versions
+pub trait ProtoName {
+  const PACKAGE: &'static str;
+  const NAME: &'static str;
+
+  /// e.g. `type.googleapis.com/google.protobuf.Duration`
+  fn type_url() -> String {
+    format!("type.googleapis.com/{}.{}", Self::PACKAGE, Self::NAME)
+  }
+}
+
+/// Encodes `msg` with prost into an `Any` with its `type.googleapis.com/...` URL
+pub fn pack_proto_any<M>(msg: M) -> Result<Any>
+where
+  M: prost::Message + ProtoName,
+{
+  let mut value = Vec::with_capacity(msg.encoded_len());
+  msg.encode(&mut value).context(result::ProtoEncode)?;
+  Ok(Any {
+    type_url: M::type_url(),
+    value,
+  })
+}
+
+/// Decodes an `Any` packed by `pack_proto_any`, the `type_url` must match `M`
+pub fn unpack_proto_any<M>(any: Any) -> Result<M>
+where
+  M: prost::Message + ProtoName + Default,
+{
+  let expected = M::type_url();
+  if any.type_url != expected {
+    return Err(result::Error::AnyTypeUrlMismatch {
+      type_url: any.type_url,
+      expected,
+    });
+  }
+  M::decode(any.value.as_slice()).context(result::ProtoDecode)
+}
+
+// Any registry
+
+/// A decoded message whose concrete type is only known at runtime
+pub trait ErasedMessage: std::fmt::Debug + Send + Sync {
+  fn as_any(&self) -> &dyn std::any::Any;
+  fn into_any(self: Box<Self>) -> Box<dyn std::any::Any>;
+}
+
+impl<M> ErasedMessage for M
+where
+  M: prost::Message + 'static,
+{
+  fn as_any(&self) -> &dyn std::any::Any {
+    self
+  }
+
+  fn into_any(self: Box<Self>) -> Box<dyn std::any::Any> {
+    self
+  }
+}
+
+impl dyn ErasedMessage {
+  pub fn downcast_ref<M: 'static>(&self) -> Option<&M> {
+    self.as_any().downcast_ref()
+  }
+
+  pub fn downcast<M: 'static>(self: Box<Self>) -> Option<M> {
+    self.into_any().downcast().ok().map(|m| *m)
+  }
+}
+
+type AnyDecoder = fn(&[u8]) -> Result<Box<dyn ErasedMessage>>;
+
+/// Maps `type_url`s to prost message types, to decode `Any` values whose type
+/// is not known at the call site
+#[derive(Default)]
+pub struct AnyRegistry {
+  decoders: HashMap<String, AnyDecoder>,
+}
+
+impl AnyRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers `M` under `M::type_url()`, replacing a previous registration
+  pub fn register<M>(&mut self) -> &mut Self
+  where
+    M: prost::Message + ProtoName + Default + 'static,
+  {
+    fn decode<M>(bytes: &[u8]) -> Result<Box<dyn ErasedMessage>>
+    where
+      M: prost::Message + Default + 'static,
+    {
+      let msg = M::decode(bytes).context(result::ProtoDecode)?;
+      Ok(Box::new(msg))
+    }
+
+    self.decoders.insert(M::type_url(), decode::<M>);
+    self
+  }
+
+  pub fn contains(&self, type_url: &str) -> bool {
+    self.decoders.contains_key(type_url)
+  }
+
+  pub fn decode(&self, any: &Any) -> Result<Box<dyn ErasedMessage>> {
+    let decode =
+      self
+        .decoders
+        .get(&any.type_url)
+        .ok_or_else(|| result::Error::AnyTypeUrlUnknown {
+          type_url: any.type_url.clone(),
+        })?;
+    decode(&any.value)
+  }
+}
+
+// Erased conversions: object-safe counterparts of the conversion traits, going through the
+// JSON representation so converters of different types fit behind `dyn`
+
+/// Packs a serializable value without naming its type, e.g. in a `Vec<Box<dyn ErasedPack>>`
+pub trait ErasedPack {
+  /// Name of the packed type, e.g. to key a registry of converters
+  fn type_name(&self) -> &'static str;
+  /// Same as `Json(&value).pack_ref()` into `google.protobuf.Value`
+  fn pack_value(&self) -> Result<Value>;
+  /// Same as `Json(&value).pack_ref()` into `google.protobuf.Any` with `JSON_TYPE_URL`
+  fn pack_any(&self) -> Result<Any>;
+}
+
+impl<T> ErasedPack for T
+where
+  T: Serialize,
+{
+  fn type_name(&self) -> &'static str {
+    std::any::type_name::<T>()
+  }
+
+  fn pack_value(&self) -> Result<Value> {
+    pack_value(self)
+  }
+
+  fn pack_any(&self) -> Result<Any> {
+    pack_any(self)
+  }
+}
+
+/// Unpacks a value whose type is only known to the converter, downcast the result to get it back
+pub trait ErasedUnpack {
+  /// Name of the unpacked type, e.g. to key a registry of converters
+  fn type_name(&self) -> &'static str;
+  fn unpack_value(&self, value: Value) -> Result<Box<dyn std::any::Any>>;
+  fn unpack_any(&self, value: &Any) -> Result<Box<dyn std::any::Any>>;
+}
+
+/// `ErasedUnpack` converter for `T`
+pub struct Unpacker<T>(std::marker::PhantomData<fn() -> T>);
+
+impl<T> Unpacker<T> {
+  pub fn new() -> Self {
+    Unpacker(std::marker::PhantomData)
+  }
+}
+
+impl<T> Default for Unpacker<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T> ErasedUnpack for Unpacker<T>
+where
+  T: for<'de> Deserialize<'de> + 'static,
+{
+  fn type_name(&self) -> &'static str {
+    std::any::type_name::<T>()
+  }
+
+  fn unpack_value(&self, value: Value) -> Result<Box<dyn std::any::Any>> {
+    unpack_value::<T>(value).map(|v| Box::new(v) as Box<dyn std::any::Any>)
+  }
+
+  fn unpack_any(&self, value: &Any) -> Result<Box<dyn std::any::Any>> {
+    unpack_any_ref::<T>(value, &[JSON_TYPE_URL]).map(|v| Box::new(v) as Box<dyn std::any::Any>)
+  }
+}
+
+// Timestamp
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+
+#[cfg(feature = "chrono")]
+impl S2ProtoPack<Timestamp> for DateTime<Utc> {
+  fn pack(self) -> Result<Timestamp> {
+    Ok(Timestamp {
+      seconds: self.timestamp(),
+      nanos: self.timestamp_subsec_nanos() as i32,
+    })
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpack<Timestamp> for DateTime<Utc> {
+  fn unpack(value: Timestamp) -> Result<DateTime<Utc>> {
+    NaiveDateTime::unpack(value).map(|dt| DateTime::from_utc(dt, Utc))
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoPack<Timestamp> for NaiveDateTime {
+  fn pack(self) -> Result<Timestamp> {
+    Ok(Timestamp {
+      seconds: self.timestamp(),
+      nanos: self.timestamp_subsec_nanos() as i32,
+    })
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpack<Timestamp> for NaiveDateTime {
+  fn unpack(Timestamp { seconds, nanos }: Timestamp) -> Result<NaiveDateTime> {
+    if !(0..NANOS_PER_SECOND).contains(&nanos) {
+      return Err(result::Error::InvalidTimestamp { seconds, nanos });
+    }
+    NaiveDateTime::from_timestamp_opt(seconds, nanos as u32)
+      .ok_or(result::Error::InvalidTimestamp { seconds, nanos })
+  }
+}
+
+/// Date-times with an offset pack the instant, the offset is not kept
+#[cfg(feature = "chrono")]
+macro_rules! impl_offset_date_time {
+  (
+    $($tz:ty => $to_tz:expr),*
+  ) => {
+    $(
+      impl S2ProtoPack<Timestamp> for DateTime<$tz> {
+        fn pack(self) -> Result<Timestamp> {
+          self.naive_utc().pack()
+        }
+      }
+
+      impl S2ProtoUnpack<Timestamp> for DateTime<$tz> {
+        fn unpack(value: Timestamp) -> Result<DateTime<$tz>> {
+          NaiveDateTime::unpack(value).map(|dt| $to_tz.from_utc_datetime(&dt))
+        }
+      }
+
+      impl_ref_by_clone!(DateTime<$tz> => Timestamp);
+      impl_option!(DateTime<$tz> => Timestamp);
+    )*
+  }
+}
+
+// `DateTime<FixedOffset>` unpacks with the UTC offset
+#[cfg(feature = "chrono")]
+impl_offset_date_time! {
+  FixedOffset => FixedOffset::east(0),
+  Local => Local
+}
+
+/// `NaiveDate` is packed as midnight UTC of that day, unpacking drops the time of day
+#[cfg(feature = "chrono")]
+impl S2ProtoPack<Timestamp> for NaiveDate {
+  fn pack(self) -> Result<Timestamp> {
+    self.and_hms(0, 0, 0).pack()
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpack<Timestamp> for NaiveDate {
+  fn unpack(value: Timestamp) -> Result<NaiveDate> {
+    NaiveDateTime::unpack(value).map(|dt| dt.date())
+  }
+}
+
+impl S2ProtoPack<Timestamp> for std::time::SystemTime {
+  fn pack(self) -> Result<Timestamp> {
+    use std::convert::TryFrom;
+    use std::time::UNIX_EPOCH;
+
+    let (after_epoch, offset) = match self.duration_since(UNIX_EPOCH) {
+      Ok(offset) => (true, offset),
+      Err(err) => (false, err.duration()),
+    };
+    let seconds =
+      i64::try_from(offset.as_secs()).map_err(|_| result::Error::SystemTimeOutOfRange)?;
+    let nanos = offset.subsec_nanos() as i32;
+    Ok(if after_epoch {
+      Timestamp { seconds, nanos }
+    } else if nanos == 0 {
+      Timestamp {
+        seconds: -seconds,
+        nanos: 0,
+      }
+    } else {
+      // nanos of a Timestamp always count forward
+      Timestamp {
+        seconds: -seconds - 1,
+        nanos: NANOS_PER_SECOND - nanos,
+      }
+    })
+  }
+}
+
+impl S2ProtoUnpack<Timestamp> for std::time::SystemTime {
+  fn unpack(Timestamp { seconds, nanos }: Timestamp) -> Result<std::time::SystemTime> {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    if !(0..NANOS_PER_SECOND).contains(&nanos) {
+      return Err(result::Error::InvalidTimestamp { seconds, nanos });
+    }
+    let seconds_offset = Duration::from_secs(seconds.unsigned_abs());
+    let time = if seconds >= 0 {
+      UNIX_EPOCH.checked_add(seconds_offset)
+    } else {
+      UNIX_EPOCH.checked_sub(seconds_offset)
+    };
+    time
+      .and_then(|time| time.checked_add(Duration::from_nanos(nanos as u64)))
+      .ok_or(result::Error::InvalidTimestamp { seconds, nanos })
+  }
+}
+
+#[cfg(feature = "time")]
+impl S2ProtoPack<Timestamp> for time::OffsetDateTime {
+  fn pack(self) -> Result<Timestamp> {
+    Ok(Timestamp {
+      seconds: self.unix_timestamp(),
+      nanos: self.nanosecond() as i32,
+    })
+  }
+}
+
+/// Unpacks in UTC
+#[cfg(feature = "time")]
+impl S2ProtoUnpack<Timestamp> for time::OffsetDateTime {
+  fn unpack(Timestamp { seconds, nanos }: Timestamp) -> Result<time::OffsetDateTime> {
+    if !(0..NANOS_PER_SECOND).contains(&nanos) {
+      return Err(result::Error::InvalidTimestamp { seconds, nanos });
+    }
+    time::OffsetDateTime::from_unix_timestamp_nanos(
+      i128::from(seconds) * i128::from(NANOS_PER_SECOND) + i128::from(nanos),
+    )
+    .map_err(|_| result::Error::InvalidTimestamp { seconds, nanos })
+  }
+}
+
+/// `PrimitiveDateTime` is assumed to be in UTC
+#[cfg(feature = "time")]
+impl S2ProtoPack<Timestamp> for time::PrimitiveDateTime {
+  fn pack(self) -> Result<Timestamp> {
+    self.assume_utc().pack()
+  }
+}
+
+#[cfg(feature = "time")]
+impl S2ProtoUnpack<Timestamp> for time::PrimitiveDateTime {
+  fn unpack(value: Timestamp) -> Result<time::PrimitiveDateTime> {
+    time::OffsetDateTime::unpack(value).map(|dt| time::PrimitiveDateTime::new(dt.date(), dt.time()))
+  }
+}
+
+impl_ref_by_clone! {
+  std::time::SystemTime => Timestamp
+}
+
+impl_option!(std::time::SystemTime => Timestamp);
+
+#[cfg(feature = "time")]
+impl_ref_by_clone! {
+  time::OffsetDateTime => Timestamp,
+  time::PrimitiveDateTime => Timestamp
+}
+
+#[cfg(feature = "time")]
+impl_option!(time::OffsetDateTime => Timestamp);
+#[cfg(feature = "time")]
+impl_option!(time::PrimitiveDateTime => Timestamp);
+
+// Duration
+
+impl S2ProtoPack<prost_types::Duration> for std::time::Duration {
+  fn pack(self) -> Result<prost_types::Duration> {
+    let seconds = self.as_secs();
+    if seconds > i64::MAX as u64 {
+      return Err(result::Error::DurationSecondsOutOfRange { seconds });
+    }
+    Ok(prost_types::Duration {
+      seconds: seconds as i64,
+      nanos: self.subsec_nanos() as i32,
+    })
+  }
+}
+
+impl S2ProtoUnpack<prost_types::Duration> for std::time::Duration {
+  fn unpack(
+    prost_types::Duration { seconds, nanos }: prost_types::Duration,
+  ) -> Result<std::time::Duration> {
+    if nanos <= -NANOS_PER_SECOND || nanos >= NANOS_PER_SECOND {
+      return Err(result::Error::DurationNanosOutOfRange { nanos });
+    }
+    if seconds < 0 || nanos < 0 {
+      return Err(result::Error::DurationNegative { seconds, nanos });
+    }
+    Ok(std::time::Duration::new(seconds as u64, nanos as u32))
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoPack<prost_types::Duration> for chrono::Duration {
+  fn pack(self) -> Result<prost_types::Duration> {
+    // `num_seconds` truncates towards zero, so the remaining nanos always share the sign of seconds
+    let seconds = self.num_seconds();
+    let nanos = (self - chrono::Duration::seconds(seconds))
+      .num_nanoseconds()
+      .unwrap_or_default();
+    Ok(prost_types::Duration {
+      seconds,
+      nanos: nanos as i32,
+    })
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpack<prost_types::Duration> for chrono::Duration {
+  fn unpack(
+    prost_types::Duration { seconds, nanos }: prost_types::Duration,
+  ) -> Result<chrono::Duration> {
+    if nanos <= -NANOS_PER_SECOND || nanos >= NANOS_PER_SECOND {
+      return Err(result::Error::DurationNanosOutOfRange { nanos });
+    }
+    if (seconds < 0 && nanos > 0) || (seconds > 0 && nanos < 0) {
+      return Err(result::Error::DurationSignMismatch { seconds, nanos });
+    }
+
+    let out_of_range = || result::Error::ParseDuration {
+      message: format!(
+        "Source duration value is out of range for the target type: seconds = {}, nanos = {}",
+        seconds, nanos
+      ),
+    };
+    let max_seconds = chrono::Duration::max_value().num_seconds();
+    if seconds > max_seconds || seconds < -max_seconds {
+      return Err(out_of_range());
+    }
+    chrono::Duration::seconds(seconds)
+      .checked_add(&chrono::Duration::nanoseconds(nanos as i64))
+      .ok_or_else(out_of_range)
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl_ref_by_clone! {
+  DateTime<Utc> => Timestamp,
+  NaiveDateTime => Timestamp,
+  NaiveDate => Timestamp,
+  chrono::Duration => prost_types::Duration
+}
+
+impl_ref_by_clone!(std::time::Duration => prost_types::Duration);
+
+#[cfg(feature = "chrono")]
+impl_option!(DateTime<Utc> => Timestamp);
+#[cfg(feature = "chrono")]
+impl_option!(NaiveDateTime => Timestamp);
+#[cfg(feature = "chrono")]
+impl_option!(NaiveDate => Timestamp);
+#[cfg(feature = "chrono")]
+impl_option!(chrono::Duration => prost_types::Duration);
+impl_option!(std::time::Duration => prost_types::Duration);
+
+// prost-wkt-types: the same well-known types as distinct structs, converted through their
+// prost-types counterparts
+
+#[cfg(feature = "prost-wkt")]
+macro_rules! impl_wkt {
+  ($wkt:ty => $proto:ty: $($(#[$meta:meta])* $rust:ty),+) => {
+    $(
+      $(#[$meta])*
+      impl S2ProtoPack<$wkt> for $rust {
+        fn pack(self) -> Result<$wkt> {
+          let packed: $proto = self.pack()?;
+          Ok(<$wkt>::from_prost(packed))
+        }
+      }
+
+      $(#[$meta])*
+      impl S2ProtoUnpack<$wkt> for $rust {
+        fn unpack(value: $wkt) -> Result<$rust> {
+          <$rust as S2ProtoUnpack<$proto>>::unpack(value.into_prost())
+        }
+      }
+
+      $(#[$meta])*
+      impl_ref_by_clone!($rust => $wkt);
+      $(#[$meta])*
+      impl_option!($rust => $wkt);
+    )+
+  };
+}
+
+/// Field by field conversion of a well-known type from/to prost-types
+#[cfg(feature = "prost-wkt")]
+trait WktType<P> {
+  fn from_prost(value: P) -> Self;
+  fn into_prost(self) -> P;
+}
+
+#[cfg(feature = "prost-wkt")]
+impl WktType<Timestamp> for prost_wkt_types::Timestamp {
+  fn from_prost(Timestamp { seconds, nanos }: Timestamp) -> Self {
+    prost_wkt_types::Timestamp { seconds, nanos }
+  }
+
+  fn into_prost(self) -> Timestamp {
+    Timestamp {
+      seconds: self.seconds,
+      nanos: self.nanos,
+    }
+  }
+}
+
+#[cfg(feature = "prost-wkt")]
+impl WktType<prost_types::Duration> for prost_wkt_types::Duration {
+  fn from_prost(prost_types::Duration { seconds, nanos }: prost_types::Duration) -> Self {
+    prost_wkt_types::Duration { seconds, nanos }
+  }
+
+  fn into_prost(self) -> prost_types::Duration {
+    prost_types::Duration {
+      seconds: self.seconds,
+      nanos: self.nanos,
+    }
+  }
+}
+
+#[cfg(feature = "prost-wkt")]
+impl_wkt! {
+  prost_wkt_types::Timestamp => Timestamp:
+  std::time::SystemTime,
+  #[cfg(feature = "chrono")]
+  DateTime<Utc>,
+  #[cfg(feature = "chrono")]
+  NaiveDateTime,
+  #[cfg(feature = "chrono")]
+  NaiveDate,
+  #[cfg(feature = "time")]
+  time::OffsetDateTime
+}
+
+#[cfg(feature = "prost-wkt")]
+impl_wkt! {
+  prost_wkt_types::Duration => prost_types::Duration:
+  std::time::Duration,
+  #[cfg(feature = "chrono")]
+  chrono::Duration
+}
+
+// Durations stored as `int64` milliseconds or seconds
+
+/// Helper type to convert `std::time::Duration` from/to `int64` milliseconds,
+/// sub-millisecond precision is dropped
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DurationMillis(pub std::time::Duration);
+
+/// Helper type to convert `std::time::Duration` from/to `int64` seconds,
+/// the fraction of a second is dropped
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DurationSeconds(pub std::time::Duration);
+
+impl S2ProtoPack<i64> for DurationMillis {
+  fn pack(self) -> Result<i64> {
+    let millis = self.0.as_millis();
+    if millis > i64::MAX as u128 {
+      return Err(result::Error::DurationMillisOutOfRange { millis });
+    }
+    Ok(millis as i64)
+  }
+}
+
+impl S2ProtoUnpack<i64> for DurationMillis {
+  fn unpack(value: i64) -> Result<DurationMillis> {
+    if value < 0 {
+      return Err(result::Error::DurationNegative {
+        seconds: value / 1000,
+        nanos: (value % 1000) as i32 * 1_000_000,
+      });
+    }
+    Ok(DurationMillis(std::time::Duration::from_millis(
+      value as u64,
+    )))
+  }
+}
+
+impl S2ProtoPack<i64> for DurationSeconds {
+  fn pack(self) -> Result<i64> {
+    let seconds = self.0.as_secs();
+    if seconds > i64::MAX as u64 {
+      return Err(result::Error::DurationSecondsOutOfRange { seconds });
+    }
+    Ok(seconds as i64)
+  }
+}
+
+impl S2ProtoUnpack<i64> for DurationSeconds {
+  fn unpack(value: i64) -> Result<DurationSeconds> {
+    if value < 0 {
+      return Err(result::Error::DurationNegative {
+        seconds: value,
+        nanos: 0,
+      });
+    }
+    Ok(DurationSeconds(std::time::Duration::from_secs(
+      value as u64,
+    )))
+  }
+}
+
+impl_ref_by_clone! {
+  DurationMillis => i64,
+  DurationSeconds => i64
+}
+
+impl_option!(DurationMillis => i64);
+impl_option!(DurationSeconds => i64);
+
+// Time of day: `int32` seconds since midnight, the fraction of a second is dropped
+
+#[cfg(feature = "chrono")]
+impl S2ProtoPack<i32> for chrono::NaiveTime {
+  fn pack(self) -> Result<i32> {
+    use chrono::Timelike;
+    Ok(self.num_seconds_from_midnight() as i32)
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpack<i32> for chrono::NaiveTime {
+  fn unpack(value: i32) -> Result<chrono::NaiveTime> {
+    if !(0..86400).contains(&value) {
+      return Err(result::Error::TimeOfDayOutOfRange { seconds: value });
+    }
+    Ok(chrono::NaiveTime::from_num_seconds_from_midnight(
+      value as u32,
+      0,
+    ))
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl_ref_by_clone! {
+  chrono::NaiveTime => i32
+}
+
+#[cfg(feature = "chrono")]
+impl_option!(chrono::NaiveTime => i32);
+
+// RFC 3339 timestamps, for messages that store a timestamp in a `string` field
+
+/// Helper type to convert `DateTime<Utc>` from/to an RFC 3339 `string`
+#[cfg(feature = "chrono")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct RfcTimestamp(pub DateTime<Utc>);
+
+#[cfg(feature = "chrono")]
+impl S2ProtoPack<String> for RfcTimestamp {
+  fn pack(self) -> Result<String> {
+    self.pack_ref()
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoPackRef<String> for RfcTimestamp {
+  fn pack_ref(&self) -> Result<String> {
+    Ok(self.0.to_rfc3339())
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpack<String> for RfcTimestamp {
+  fn unpack(value: String) -> Result<RfcTimestamp> {
+    RfcTimestamp::unpack_ref(&value)
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl S2ProtoUnpackRef<String> for RfcTimestamp {
+  fn unpack_ref(value: &String) -> Result<RfcTimestamp> {
+    DateTime::parse_from_rfc3339(value)
+      .context(result::TimestampParse)
+      .map(|dt| RfcTimestamp(dt.with_timezone(&Utc)))
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl_option!(RfcTimestamp => String);
+
+// BigDecimal
+
+#[cfg(feature = "bigdecimal")]
+impl S2ProtoPack<String> for BigDecimal {
+  fn pack(self) -> Result<String> {
+    Ok(self.to_string())
+  }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl S2ProtoPackRef<String> for BigDecimal {
+  fn pack_ref(&self) -> Result<String> {
+    Ok(self.to_string())
+  }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl<T> S2ProtoUnpack<T> for BigDecimal
+where
+  T: AsRef<str>,
+{
+  fn unpack(v: T) -> Result<BigDecimal> {
+    v.as_ref().parse().context(result::ParseBigDecimal)
+  }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl<T> S2ProtoUnpackRef<T> for BigDecimal
+where
+  T: AsRef<str>,
+{
+  fn unpack_ref(v: &T) -> Result<BigDecimal> {
+    v.as_ref().parse().context(result::ParseBigDecimal)
+  }
+}
+
+// Unpacking from any `AsRef<str>` rules out `BigDecimal: S2ProtoUnpack<Option<String>>`,
+// `Option<BigDecimal>` or `required` cover optional fields
+#[cfg(feature = "bigdecimal")]
+impl_option!(@pack BigDecimal => String);
+
+// Decimal
+
+#[cfg(feature = "decimal")]
+impl S2ProtoPack<String> for rust_decimal::Decimal {
+  fn pack(self) -> Result<String> {
+    Ok(self.to_string())
+  }
+}
+
+#[cfg(feature = "decimal")]
+impl S2ProtoPackRef<String> for rust_decimal::Decimal {
+  fn pack_ref(&self) -> Result<String> {
+    Ok(self.to_string())
+  }
+}
+
+#[cfg(feature = "decimal")]
+impl S2ProtoUnpack<String> for rust_decimal::Decimal {
+  fn unpack(value: String) -> Result<rust_decimal::Decimal> {
+    value.parse().context(result::Decimal)
+  }
+}
+
+#[cfg(feature = "decimal")]
+impl S2ProtoUnpackRef<String> for rust_decimal::Decimal {
+  fn unpack_ref(value: &String) -> Result<rust_decimal::Decimal> {
+    value.parse().context(result::Decimal)
+  }
+}
+
+#[cfg(feature = "decimal")]
+impl_option!(rust_decimal::Decimal => String);
+
+// Uuid
+
+#[cfg(feature = "uuid")]
+impl S2ProtoPack<String> for uuid::Uuid {
+  fn pack(self) -> Result<String> {
+    Ok(self.to_hyphenated().to_string())
+  }
+}
+
+#[cfg(feature = "uuid")]
+impl S2ProtoUnpack<String> for uuid::Uuid {
+  fn unpack(v: String) -> Result<uuid::Uuid> {
+    uuid::Uuid::parse_str(&v).context(result::Uuid)
+  }
+}
+
+#[cfg(feature = "uuid")]
+impl S2ProtoPack<Vec<u8>> for uuid::Uuid {
+  fn pack(self) -> Result<Vec<u8>> {
+    Ok(self.as_bytes().to_vec())
+  }
+}
+
+#[cfg(feature = "uuid")]
+impl S2ProtoUnpack<Vec<u8>> for uuid::Uuid {
+  fn unpack(v: Vec<u8>) -> Result<uuid::Uuid> {
+    if v.len() != 16 {
+      return Err(result::Error::UuidBytesLength { len: v.len() });
+    }
+    uuid::Uuid::from_slice(&v).context(result::Uuid)
+  }
+}
+
+#[cfg(feature = "uuid")]
+impl_ref_by_clone! {
+  uuid::Uuid => String,
+  uuid::Uuid => Vec<u8>
+}
+
+#[cfg(feature = "uuid")]
+impl_option!(uuid::Uuid => String);
+#[cfg(feature = "uuid")]
+impl_option!(uuid::Uuid => Vec<u8>);
+
+// Secrets: packing exposes the secret only to move it into the message
+
+#[cfg(feature = "secrecy")]
+impl S2ProtoPack<String> for secrecy::Secret<String> {
+  fn pack(self) -> Result<String> {
+    use secrecy::ExposeSecret;
+    Ok(self.expose_secret().clone())
+  }
+}
+
+#[cfg(feature = "secrecy")]
+impl S2ProtoPackRef<String> for secrecy::Secret<String> {
+  fn pack_ref(&self) -> Result<String> {
+    use secrecy::ExposeSecret;
+    Ok(self.expose_secret().clone())
+  }
+}
+
+#[cfg(feature = "secrecy")]
+impl S2ProtoUnpack<String> for secrecy::Secret<String> {
+  fn unpack(value: String) -> Result<secrecy::Secret<String>> {
+    Ok(secrecy::Secret::new(value))
+  }
+}
+
+#[cfg(feature = "secrecy")]
+impl S2ProtoUnpackRef<String> for secrecy::Secret<String> {
+  fn unpack_ref(value: &String) -> Result<secrecy::Secret<String>> {
+    Ok(secrecy::Secret::new(value.clone()))
+  }
+}
+
+#[cfg(feature = "secrecy")]
+impl_option!(secrecy::Secret<String> => String);
+
+// Parsed from string
+
+/// Implements conversions from/to `string` using `Display` and `FromStr`,
+/// parse errors are wrapped with the given context selector
+macro_rules! impl_parse {
+  (
+    $($ty:ty => $context:ident),*
+  ) => {
+    $(
+      impl S2ProtoPack<String> for $ty {
+        fn pack(self) -> Result<String> {
+          Ok(self.to_string())
+        }
+      }
+
+      impl S2ProtoPackRef<String> for $ty {
+        fn pack_ref(&self) -> Result<String> {
+          Ok(self.to_string())
+        }
+      }
+
+      impl S2ProtoUnpack<String> for $ty {
+        fn unpack(value: String) -> Result<$ty> {
+          value.parse().context(result::$context)
+        }
+      }
+
+      impl S2ProtoUnpackRef<String> for $ty {
+        fn unpack_ref(value: &String) -> Result<$ty> {
+          value.parse().context(result::$context)
+        }
+      }
+
+      impl_option!($ty => String);
+    )*
+  }
+}
+
+impl_parse! {
+  std::net::IpAddr => AddrParse,
+  std::net::Ipv4Addr => AddrParse,
+  std::net::Ipv6Addr => AddrParse,
+  std::net::SocketAddr => AddrParse,
+  u128 => ParseInt,
+  i128 => ParseInt,
+  char => ParseChar
+}
+
+// Wrappers
+
+macro_rules! impl_self {
+  (
+    $($ty:ty),*
+  ) => {
+    $(
+      impl_self!(@convert $ty);
+      impl_option!($ty => $ty);
+    )*
+  };
+  (@convert $ty:ty) => {
+      impl S2ProtoPack<$ty> for $ty {
+        fn pack(self) -> Result<$ty> {
+          Ok(self)
+        }
+      }
+
+      impl S2ProtoPackRef<$ty> for $ty {
+        fn pack_ref(&self) -> Result<$ty> {
+          Ok(self.clone())
+        }
+      }
+
+      impl S2ProtoUnpack<$ty> for $ty {
+        fn unpack(value: $ty) -> Result<$ty> {
+          Ok(value)
+        }
+      }
+
+      impl S2ProtoUnpackRef<$ty> for $ty {
+        fn unpack_ref(value: &$ty) -> Result<$ty> {
+          Ok(value.clone())
+        }
+      }
+  };
+}
+
+impl_self! {
+  f32,
+  f64,
+  i64,
+  u64,
+  i32,
+  u32,
+  bool,
+  String
+}
+
+// `Option<Vec<u8>>` is covered by the repeated value impls
+impl_self!(@convert Vec<u8>);
+
+/// Implements the conversions of a newtype `$rust` by delegating to the wrapped
+/// type, e.g. `s2_transparent!(UserId => u64)` for `struct UserId(u64)`
+#[macro_export]
+macro_rules! s2_transparent {
+  (
+    $($rust:ty => $proto:ty),* $(,)?
+  ) => {
+    $(
+      impl $crate::S2ProtoPack<$proto> for $rust {
+        fn pack(self) -> $crate::result::Result<$proto> {
+          $crate::S2ProtoPack::pack(self.0)
+        }
+      }
+
+      impl $crate::S2ProtoPackRef<$proto> for $rust {
+        fn pack_ref(&self) -> $crate::result::Result<$proto> {
+          $crate::S2ProtoPackRef::pack_ref(&self.0)
+        }
+      }
+
+      impl $crate::S2ProtoUnpack<$proto> for $rust {
+        fn unpack(value: $proto) -> $crate::result::Result<$rust> {
+          $crate::S2ProtoUnpack::unpack(value).map(|value| Self(value))
+        }
+      }
+
+      impl $crate::S2ProtoUnpackRef<$proto> for $rust {
+        fn unpack_ref(value: &$proto) -> $crate::result::Result<$rust> {
+          $crate::S2ProtoUnpackRef::unpack_ref(value).map(|value| Self(value))
+        }
+      }
+
+      $crate::impl_option!($rust => $proto);
+    )*
+  };
+}
+
+// OS strings pack into `string` only if they are valid UTF-8
+
+macro_rules! impl_os_string {
+  (
+    $($ty:ty),*
+  ) => {
+    $(
+      impl S2ProtoPack<String> for $ty {
+        fn pack(self) -> Result<String> {
+          std::ffi::OsString::from(self)
+            .into_string()
+            .map_err(|s| result::Error::NonUtf8Path {
+              path: s.to_string_lossy().into_owned(),
+            })
+        }
+      }
+
+      impl S2ProtoPackRef<String> for $ty {
+        fn pack_ref(&self) -> Result<String> {
+          self
+            .to_str()
+            .map(ToString::to_string)
+            .ok_or_else(|| result::Error::NonUtf8Path {
+              path: self.to_string_lossy().into_owned(),
+            })
+        }
+      }
+
+      impl S2ProtoUnpack<String> for $ty {
+        fn unpack(value: String) -> Result<$ty> {
+          Ok(<$ty>::from(value))
+        }
+      }
+
+      impl S2ProtoUnpackRef<String> for $ty {
+        fn unpack_ref(value: &String) -> Result<$ty> {
+          Ok(<$ty>::from(value))
+        }
+      }
+
+      impl_option!($ty => String);
+    )*
+  }
+}
+
+impl_os_string! {
+  std::path::PathBuf,
+  std::ffi::OsString
+}
+
+// Borrowed strings only pack, unpacking always produces a `String`
+
+macro_rules! impl_pack_str {
+  (
+    $($ty:ty),*
+  ) => {
+    $(
+      impl S2ProtoPack<String> for $ty {
+        fn pack(self) -> Result<String> {
+          Ok(self.to_string())
+        }
+      }
+
+      impl S2ProtoPackRef<String> for $ty {
+        fn pack_ref(&self) -> Result<String> {
+          Ok(self.to_string())
+        }
+      }
+
+      impl S2ProtoPack<Option<String>> for $ty {
+        fn pack(self) -> Result<Option<String>> {
+          Ok(Some(self.to_string()))
+        }
+      }
+
+      impl S2ProtoPackRef<Option<String>> for $ty {
+        fn pack_ref(&self) -> Result<Option<String>> {
+          Ok(Some(self.to_string()))
+        }
+      }
+    )*
+  }
+}
+
+impl_pack_str! {
+  &str,
+  Cow<'_, str>
+}
+
+// Empty
+
+// prost represents `google.protobuf.Empty` as `()`
+impl_self! {
+  ()
+}
+
+// FieldMask
+
+/// Paths of a `google.protobuf.FieldMask`, e.g. `["name", "address.city"]`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FieldMask(pub Vec<String>);
+
+impl FieldMask {
+  /// Whether `path` is covered by the mask, either listed itself or below a listed path,
+  /// e.g. `a.b` is covered by `a`
+  pub fn contains(&self, path: &str) -> bool {
+    self
+      .0
+      .iter()
+      .any(|p| path == p || (path.starts_with(p.as_str()) && path[p.len()..].starts_with('.')))
+  }
+}
+
+impl S2ProtoPack<prost_types::FieldMask> for FieldMask {
+  fn pack(self) -> Result<prost_types::FieldMask> {
+    Ok(prost_types::FieldMask { paths: self.0 })
+  }
+}
+
+impl S2ProtoPackRef<prost_types::FieldMask> for FieldMask {
+  fn pack_ref(&self) -> Result<prost_types::FieldMask> {
+    Ok(prost_types::FieldMask {
+      paths: self.0.clone(),
+    })
+  }
+}
+
+impl S2ProtoUnpack<prost_types::FieldMask> for FieldMask {
+  fn unpack(value: prost_types::FieldMask) -> Result<FieldMask> {
+    Ok(FieldMask(value.paths))
+  }
+}
+
+impl S2ProtoUnpackRef<prost_types::FieldMask> for FieldMask {
+  fn unpack_ref(value: &prost_types::FieldMask) -> Result<FieldMask> {
+    Ok(FieldMask(value.paths.clone()))
+  }
+}
+
+impl_option!(FieldMask => prost_types::FieldMask);
+
+// Narrow integers
+
+macro_rules! impl_narrow_int {
+  (
+    $($rust:ty => $proto:ty),*
+  ) => {
+    $(
+      impl S2ProtoPack<$proto> for $rust {
+        fn pack(self) -> Result<$proto> {
+          Ok(<$proto>::from(self))
+        }
+      }
+
+      impl S2ProtoPackRef<$proto> for $rust {
+        fn pack_ref(&self) -> Result<$proto> {
+          Ok(<$proto>::from(*self))
+        }
+      }
+
+      impl S2ProtoUnpack<$proto> for $rust {
+        fn unpack(value: $proto) -> Result<$rust> {
+          use std::convert::TryFrom;
+
+          <$rust>::try_from(value).map_err(|_| result::Error::IntegerOverflow {
+            value: i64::from(value),
+            target: stringify!($rust),
+          })
+        }
+      }
+
+      impl S2ProtoUnpackRef<$proto> for $rust {
+        fn unpack_ref(value: &$proto) -> Result<$rust> {
+          <$rust>::unpack(*value)
+        }
+      }
+
+      impl_option!($rust => $proto);
+    )*
+  }
+}
+
+impl_narrow_int! {
+  i8 => i32,
+  i16 => i32,
+  u8 => u32,
+  u16 => u32
+}
+
+// Half floats
+
+/// `f16` widens losslessly to `float`, unpacking rounds to the nearest `f16`
+#[cfg(feature = "half")]
+impl S2ProtoPack<f32> for half::f16 {
+  fn pack(self) -> Result<f32> {
+    Ok(self.to_f32())
+  }
+}
+
+#[cfg(feature = "half")]
+impl S2ProtoUnpack<f32> for half::f16 {
+  fn unpack(value: f32) -> Result<half::f16> {
+    Ok(half::f16::from_f32(value))
+  }
+}
+
+#[cfg(feature = "half")]
+impl_ref_by_clone!(half::f16 => f32);
+
+#[cfg(feature = "half")]
+impl_option!(half::f16 => f32);
+
+// Ordered floats convert like the float they wrap
+
+#[cfg(feature = "ordered-float")]
+macro_rules! impl_ordered_float {
+  ($($ty:ty),+) => {
+    $(
+      impl S2ProtoPack<$ty> for ordered_float::OrderedFloat<$ty> {
+        fn pack(self) -> Result<$ty> {
+          Ok(self.into_inner())
+        }
+      }
+
+      impl S2ProtoUnpack<$ty> for ordered_float::OrderedFloat<$ty> {
+        fn unpack(value: $ty) -> Result<ordered_float::OrderedFloat<$ty>> {
+          Ok(ordered_float::OrderedFloat(value))
+        }
+      }
+
+      impl_ref_by_clone!(ordered_float::OrderedFloat<$ty> => $ty);
+      impl_option!(ordered_float::OrderedFloat<$ty> => $ty);
+    )+
+  };
+}
+
+#[cfg(feature = "ordered-float")]
+impl_ordered_float!(f32, f64);
+
+// Non-zero integers
+
+macro_rules! impl_non_zero {
+  (
+    $($rust:ty => $proto:ty),*
+  ) => {
+    $(
+      impl S2ProtoPack<$proto> for $rust {
+        fn pack(self) -> Result<$proto> {
+          Ok(self.get())
+        }
+      }
+
+      impl S2ProtoPackRef<$proto> for $rust {
+        fn pack_ref(&self) -> Result<$proto> {
+          Ok(self.get())
+        }
+      }
+
+      impl S2ProtoUnpack<$proto> for $rust {
+        fn unpack(value: $proto) -> Result<$rust> {
+          <$rust>::new(value).ok_or(result::Error::ZeroValue {
+            target: stringify!($rust),
+          })
+        }
+      }
+
+      impl S2ProtoUnpackRef<$proto> for $rust {
+        fn unpack_ref(value: &$proto) -> Result<$rust> {
+          <$rust>::unpack(*value)
+        }
+      }
+
+      impl_option!($rust => $proto);
+    )*
+  }
+}
+
+impl_non_zero! {
+  std::num::NonZeroU32 => u32,
+  std::num::NonZeroU64 => u64,
+  std::num::NonZeroI32 => i32,
+  std::num::NonZeroI64 => i64
+}
+
+// Wrapping and saturating integers convert like the integer they wrap
+
+macro_rules! impl_num_wrapper {
+  (
+    $($wrapper:ident<$int:ty>),*
+  ) => {
+    $(
+      impl S2ProtoPack<$int> for std::num::$wrapper<$int> {
+        fn pack(self) -> Result<$int> {
+          Ok(self.0)
+        }
+      }
+
+      impl S2ProtoPackRef<$int> for std::num::$wrapper<$int> {
+        fn pack_ref(&self) -> Result<$int> {
+          Ok(self.0)
+        }
+      }
+
+      impl S2ProtoUnpack<$int> for std::num::$wrapper<$int> {
+        fn unpack(value: $int) -> Result<std::num::$wrapper<$int>> {
+          Ok(std::num::$wrapper(value))
+        }
+      }
+
+      impl S2ProtoUnpackRef<$int> for std::num::$wrapper<$int> {
+        fn unpack_ref(value: &$int) -> Result<std::num::$wrapper<$int>> {
+          Ok(std::num::$wrapper(*value))
+        }
+      }
+
+      impl_option!(std::num::$wrapper<$int> => $int);
+    )*
+  }
+}
+
+impl_num_wrapper! {
+  Wrapping<u32>,
+  Wrapping<u64>,
+  Wrapping<i32>,
+  Wrapping<i64>,
+  Saturating<u32>,
+  Saturating<u64>,
+  Saturating<i32>,
+  Saturating<i64>
+}
+
+// bytes
+
+#[cfg(feature = "bytes")]
+impl_self! {
+  bytes::Bytes
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoPack<Vec<u8>> for bytes::Bytes {
+  fn pack(self) -> Result<Vec<u8>> {
+    Ok(self.to_vec())
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoUnpack<Vec<u8>> for bytes::Bytes {
+  fn unpack(value: Vec<u8>) -> Result<bytes::Bytes> {
+    Ok(value.into())
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoPack<bytes::Bytes> for Vec<u8> {
+  fn pack(self) -> Result<bytes::Bytes> {
+    Ok(self.into())
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoUnpack<bytes::Bytes> for Vec<u8> {
+  fn unpack(value: bytes::Bytes) -> Result<Vec<u8>> {
+    Ok(value.to_vec())
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl_ref_by_clone! {
+  bytes::Bytes => Vec<u8>,
+  Vec<u8> => bytes::Bytes
+}
+
+// prost generates `bytes::Bytes` for `string` fields with the `bytes` config option
+
+#[cfg(feature = "bytes")]
+impl S2ProtoPack<bytes::Bytes> for String {
+  fn pack(self) -> Result<bytes::Bytes> {
+    Ok(self.into())
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoUnpack<bytes::Bytes> for String {
+  fn unpack(value: bytes::Bytes) -> Result<String> {
+    String::unpack_ref(&value)
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoUnpackRef<bytes::Bytes> for String {
+  fn unpack_ref(value: &bytes::Bytes) -> Result<String> {
+    std::str::from_utf8(value)
+      .map(ToOwned::to_owned)
+      .context(result::NonUtf8)
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl S2ProtoPackRef<bytes::Bytes> for String {
+  fn pack_ref(&self) -> Result<bytes::Bytes> {
+    Ok(bytes::Bytes::copy_from_slice(self.as_bytes()))
+  }
+}
+
+// Borrowed bytes: unpacking through the traits always owns the result,
+// `unpack_cow` borrows from the message instead
+
+impl S2ProtoPack<Vec<u8>> for Cow<'_, [u8]> {
+  fn pack(self) -> Result<Vec<u8>> {
+    Ok(self.into_owned())
+  }
+}
+
+impl S2ProtoPackRef<Vec<u8>> for Cow<'_, [u8]> {
+  fn pack_ref(&self) -> Result<Vec<u8>> {
+    Ok(self.to_vec())
+  }
+}
+
+/// Moves the message's buffer into `Cow::Owned`, no copy
+impl S2ProtoUnpack<Vec<u8>> for Cow<'static, [u8]> {
+  fn unpack(value: Vec<u8>) -> Result<Cow<'static, [u8]>> {
+    Ok(Cow::Owned(value))
+  }
+}
+
+/// Always copies the bytes: `unpack_ref` can't tie its result to the lifetime of the
+/// message, so the `Cow` can't borrow from it. Derived `unpack_ref` goes through this impl,
+/// read large payloads with `unpack_cow` to avoid the copy
+impl S2ProtoUnpackRef<Vec<u8>> for Cow<'static, [u8]> {
+  fn unpack_ref(value: &Vec<u8>) -> Result<Cow<'static, [u8]>> {
+    Ok(Cow::Owned(value.clone()))
+  }
+}
+
+impl_option!(Cow<'static, [u8]> => Vec<u8>);
+
+#[cfg(feature = "bytes")]
+impl S2ProtoPack<bytes::Bytes> for Cow<'_, [u8]> {
+  fn pack(self) -> Result<bytes::Bytes> {
+    Ok(self.into_owned().into())
+  }
+}
+
+/// Borrows the bytes of a `bytes`/`Vec<u8>` message field without copying.
+/// This is the only zero-copy route, unpacking through the traits (and so the derive)
+/// always copies by reference
+pub fn unpack_cow<P>(value: &P) -> Cow<'_, [u8]>
+where
+  P: AsRef<[u8]> + ?Sized,
+{
+  Cow::Borrowed(value.as_ref())
+}
+
+// base64
+
+/// Helper type to convert bytes from/to a standard base64 `string`
+#[cfg(feature = "base64")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Base64(pub Vec<u8>);
+
+#[cfg(feature = "base64")]
+impl S2ProtoPack<String> for Base64 {
+  fn pack(self) -> Result<String> {
+    self.pack_ref()
+  }
+}
+
+#[cfg(feature = "base64")]
+impl S2ProtoPackRef<String> for Base64 {
+  fn pack_ref(&self) -> Result<String> {
+    Ok(base64::encode(&self.0))
+  }
+}
+
+#[cfg(feature = "base64")]
+impl S2ProtoUnpack<String> for Base64 {
+  fn unpack(value: String) -> Result<Base64> {
+    Base64::unpack_ref(&value)
+  }
+}
+
+#[cfg(feature = "base64")]
+impl S2ProtoUnpackRef<String> for Base64 {
+  fn unpack_ref(value: &String) -> Result<Base64> {
+    base64::decode(value).context(result::Base64).map(Base64)
+  }
+}
+
+#[cfg(feature = "base64")]
+impl_option!(Base64 => String);
+
+/// Serializes as the base64 string, so bytes inside `Json<T>` don't become an array of numbers
+#[cfg(feature = "base64")]
+impl Serialize for Base64 {
+  fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    serializer.serialize_str(&base64::encode(&self.0))
+  }
+}
+
+#[cfg(feature = "base64")]
+impl<'de> Deserialize<'de> for Base64 {
+  fn deserialize<D>(deserializer: D) -> std::result::Result<Base64, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    let value = String::deserialize(deserializer)?;
+    base64::decode(&value)
+      .map(Base64)
+      .map_err(serde::de::Error::custom)
+  }
+}
+
+// repeated value
+
+impl<T, T2> S2ProtoPack<Vec<T>> for Vec<T2>
+where
+  T2: S2ProtoPack<T>,
+{
+  fn pack(self) -> Result<Vec<T>> {
+    let mut r = Vec::with_capacity(self.len());
+    for (i, elem) in self.into_iter().enumerate() {
+      let item = elem.pack().map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl<T, T2> S2ProtoPackRef<Vec<T>> for Vec<T2>
+where
+  T2: S2ProtoPackRef<T>,
+{
+  fn pack_ref(&self) -> Result<Vec<T>> {
+    let mut r = Vec::with_capacity(self.len());
+    for (i, elem) in self.iter().enumerate() {
+      let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl<T, T2> S2ProtoUnpack<Vec<T>> for Vec<T2>
+where
+  T2: S2ProtoUnpack<T>,
+{
+  fn unpack(value: Vec<T>) -> Result<Vec<T2>> {
+    let mut r = Vec::with_capacity(value.len());
+    for (i, elem) in value.into_iter().enumerate() {
+      let item = T2::unpack(elem).map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl<T, T2> S2ProtoUnpackRef<Vec<T>> for Vec<T2>
+where
+  T2: S2ProtoUnpackRef<T>,
+{
+  fn unpack_ref(value: &Vec<T>) -> Result<Vec<T2>> {
+    let mut r = Vec::with_capacity(value.len());
+    for (i, elem) in value.iter().enumerate() {
+      let item = T2::unpack_ref(elem).map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl_option!([T, T2] Vec<T2> => Vec<T>);
+
+impl<T, T2> S2ProtoPack<Vec<T>> for VecDeque<T2>
+where
+  T2: S2ProtoPack<T>,
+{
+  fn pack(self) -> Result<Vec<T>> {
+    Vec::from(self).pack()
+  }
+}
+
+impl<T, T2> S2ProtoPackRef<Vec<T>> for VecDeque<T2>
+where
+  T2: S2ProtoPackRef<T>,
+{
+  fn pack_ref(&self) -> Result<Vec<T>> {
+    let mut r = Vec::with_capacity(self.len());
+    for (i, elem) in self.iter().enumerate() {
+      let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl<T, T2> S2ProtoUnpack<Vec<T>> for VecDeque<T2>
+where
+  T2: S2ProtoUnpack<T>,
+{
+  fn unpack(value: Vec<T>) -> Result<VecDeque<T2>> {
+    Vec::<T2>::unpack(value).map(VecDeque::from)
+  }
+}
+
+impl<T, T2> S2ProtoUnpackRef<Vec<T>> for VecDeque<T2>
+where
+  T2: S2ProtoUnpackRef<T>,
+{
+  fn unpack_ref(value: &Vec<T>) -> Result<VecDeque<T2>> {
+    Vec::<T2>::unpack_ref(value).map(VecDeque::from)
+  }
+}
+
+impl_option!([T, T2] VecDeque<T2> => Vec<T>);
+
+// Arrays unpack only from a repeated field of exactly `N` elements
+
+impl<T, T2, const N: usize> S2ProtoPack<Vec<T>> for [T2; N]
+where
+  T2: S2ProtoPack<T>,
+{
+  fn pack(self) -> Result<Vec<T>> {
+    Vec::from(self).pack()
+  }
+}
+
+impl<T, T2, const N: usize> S2ProtoPackRef<Vec<T>> for [T2; N]
+where
+  T2: S2ProtoPackRef<T>,
+{
+  fn pack_ref(&self) -> Result<Vec<T>> {
+    let mut r = Vec::with_capacity(N);
+    for (i, elem) in self.iter().enumerate() {
+      let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl<T, T2, const N: usize> S2ProtoUnpack<Vec<T>> for [T2; N]
+where
+  T2: S2ProtoUnpack<T>,
+{
+  fn unpack(value: Vec<T>) -> Result<[T2; N]> {
+    use std::convert::TryFrom;
+    ensure_length::<N>(value.len())?;
+    let r = Vec::<T2>::unpack(value)?;
+    Ok(<[T2; N]>::try_from(r).unwrap_or_else(|_| unreachable!()))
+  }
+}
+
+impl<T, T2, const N: usize> S2ProtoUnpackRef<Vec<T>> for [T2; N]
+where
+  T2: S2ProtoUnpackRef<T>,
+{
+  fn unpack_ref(value: &Vec<T>) -> Result<[T2; N]> {
+    use std::convert::TryFrom;
+    ensure_length::<N>(value.len())?;
+    let r = Vec::<T2>::unpack_ref(value)?;
+    Ok(<[T2; N]>::try_from(r).unwrap_or_else(|_| unreachable!()))
+  }
+}
+
+// `u8` has no conversion of its own, so byte arrays map to `bytes`
+
+impl<const N: usize> S2ProtoPack<Vec<u8>> for [u8; N] {
+  fn pack(self) -> Result<Vec<u8>> {
+    Ok(self.to_vec())
+  }
+}
+
+impl<const N: usize> S2ProtoPackRef<Vec<u8>> for [u8; N] {
+  fn pack_ref(&self) -> Result<Vec<u8>> {
+    Ok(self.to_vec())
+  }
+}
+
+impl<const N: usize> S2ProtoUnpack<Vec<u8>> for [u8; N] {
+  fn unpack(value: Vec<u8>) -> Result<[u8; N]> {
+    Self::unpack_ref(&value)
+  }
+}
+
+impl<const N: usize> S2ProtoUnpackRef<Vec<u8>> for [u8; N] {
+  fn unpack_ref(value: &Vec<u8>) -> Result<[u8; N]> {
+    use std::convert::TryFrom;
+    ensure_length::<N>(value.len())?;
+    Ok(<[u8; N]>::try_from(&value[..]).unwrap_or_else(|_| unreachable!()))
+  }
+}
+
+fn ensure_length<const N: usize>(actual: usize) -> Result<()> {
+  if actual == N {
+    Ok(())
+  } else {
+    Err(result::Error::WrongLength {
+      expected: N,
+      actual,
+    })
+  }
+}
+
+// Borrowed slices pack element by element with `pack_ref`, without cloning the slice
+
+impl<T, T2> S2ProtoPack<Vec<T>> for &[T2]
+where
+  T2: S2ProtoPackRef<T>,
+{
+  fn pack(self) -> Result<Vec<T>> {
+    let mut r = Vec::with_capacity(self.len());
+    for (i, elem) in self.iter().enumerate() {
+      let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
+impl<T, T2> S2ProtoPackRef<Vec<T>> for &[T2]
+where
+  T2: S2ProtoPackRef<T>,
+{
+  fn pack_ref(&self) -> Result<Vec<T>> {
+    (*self).pack()
+  }
+}
+
+/// Packs the elements of `iter` into a `Vec`, stopping at the first error
+pub fn pack_iter<I, R, P>(iter: I) -> Result<Vec<P>>
+where
+  I: IntoIterator<Item = R>,
+  R: S2ProtoPack<P>,
+{
+  PackIter::new(iter).collect()
+}
+
+/// Lazily packs the elements of an iterator, yielding `Result<P>` for each one,
+/// e.g. to feed a streaming response
+pub struct PackIter<I, P> {
+  iter: I,
+  index: usize,
+  _proto: std::marker::PhantomData<fn() -> P>,
+}
+
+impl<I, P> PackIter<I, P>
+where
+  I: Iterator,
+  I::Item: S2ProtoPack<P>,
+{
+  pub fn new<T>(iter: T) -> Self
+  where
+    T: IntoIterator<IntoIter = I, Item = I::Item>,
+  {
+    PackIter {
+      iter: iter.into_iter(),
+      index: 0,
+      _proto: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<I, P> Iterator for PackIter<I, P>
+where
+  I: Iterator,
+  I::Item: S2ProtoPack<P>,
+{
+  type Item = Result<P>;
+
+  fn next(&mut self) -> Option<Result<P>> {
+    let elem = self.iter.next()?;
+    let index = self.index;
+    self.index += 1;
+    Some(elem.pack().map_err(|e| result::Error::ListElement {
+      source: Box::new(e),
+      index,
+    }))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.iter.size_hint()
+  }
+}
+
+/// Lazily unpacks the elements of a repeated field, see `UnpackIter`
+pub fn unpack_iter<I, P, R>(iter: I) -> UnpackIter<I::IntoIter, R>
+where
+  I: IntoIterator<Item = P>,
+  R: S2ProtoUnpack<P>,
+{
+  UnpackIter::new(iter)
+}
+
+/// Lazily unpacks the elements of an iterator, yielding `Result<R>` for each one.
+/// An element error does not end the iteration, the following elements are still unpacked
+pub struct UnpackIter<I, R> {
+  iter: I,
+  index: usize,
+  _rust: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<I, R> UnpackIter<I, R>
+where
+  I: Iterator,
+  R: S2ProtoUnpack<I::Item>,
+{
+  pub fn new<T>(iter: T) -> Self
+  where
+    T: IntoIterator<IntoIter = I, Item = I::Item>,
+  {
+    UnpackIter {
+      iter: iter.into_iter(),
+      index: 0,
+      _rust: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<I, R> Iterator for UnpackIter<I, R>
+where
+  I: Iterator,
+  R: S2ProtoUnpack<I::Item>,
+{
+  type Item = Result<R>;
+
+  fn next(&mut self) -> Option<Result<R>> {
+    let elem = self.iter.next()?;
+    let index = self.index;
+    self.index += 1;
+    Some(R::unpack(elem).map_err(|e| result::Error::ListElement {
+      source: Box::new(e),
+      index,
+    }))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.iter.size_hint()
+  }
+}
+
+// set
+
+/// Helper type to unpack a repeated field into a set, failing with
+/// `DuplicateSetValue` instead of dropping repeated elements
+pub struct StrictSet<S>(pub S);
+
+macro_rules! impl_set {
+  (
+    $($set:ident: $($bound:ident),+);*
+  ) => {
+    $(
+      impl<T, T2> S2ProtoPack<Vec<T>> for $set<T2>
+      where
+        T2: S2ProtoPack<T>,
+      {
+        fn pack(self) -> Result<Vec<T>> {
+          self.into_iter().collect::<Vec<_>>().pack()
+        }
+      }
+
+      impl<T, T2> S2ProtoPackRef<Vec<T>> for $set<T2>
+      where
+        T2: S2ProtoPackRef<T>,
+      {
+        fn pack_ref(&self) -> Result<Vec<T>> {
+          let mut r = Vec::with_capacity(self.len());
+          for (i, elem) in self.iter().enumerate() {
+            let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+              source: Box::new(e),
+              index: i,
+            })?;
+            r.push(item);
+          }
+          Ok(r)
+        }
+      }
+
+      impl<T, T2> S2ProtoUnpack<Vec<T>> for $set<T2>
+      where
+        T2: S2ProtoUnpack<T> $(+ $bound)+,
+      {
+        fn unpack(value: Vec<T>) -> Result<$set<T2>> {
+          Vec::<T2>::unpack(value).map(|items| items.into_iter().collect())
+        }
+      }
+
+      impl<T, T2> S2ProtoUnpackRef<Vec<T>> for $set<T2>
+      where
+        T2: S2ProtoUnpackRef<T> $(+ $bound)+,
+      {
+        fn unpack_ref(value: &Vec<T>) -> Result<$set<T2>> {
+          Vec::<T2>::unpack_ref(value).map(|items| items.into_iter().collect())
+        }
+      }
+
+      impl<T, T2> S2ProtoPack<Vec<T>> for StrictSet<$set<T2>>
+      where
+        T2: S2ProtoPack<T>,
+      {
+        fn pack(self) -> Result<Vec<T>> {
+          self.0.pack()
+        }
+      }
+
+      impl<T, T2> S2ProtoPackRef<Vec<T>> for StrictSet<$set<T2>>
+      where
+        T2: S2ProtoPackRef<T>,
+      {
+        fn pack_ref(&self) -> Result<Vec<T>> {
+          self.0.pack_ref()
+        }
+      }
+
+      impl<T, T2> S2ProtoUnpack<Vec<T>> for StrictSet<$set<T2>>
+      where
+        T2: S2ProtoUnpack<T> $(+ $bound)+,
+      {
+        fn unpack(value: Vec<T>) -> Result<StrictSet<$set<T2>>> {
+          let mut r = $set::new();
+          for (i, elem) in value.into_iter().enumerate() {
+            let item = T2::unpack(elem).map_err(|e| result::Error::ListElement {
+              source: Box::new(e),
+              index: i,
+            })?;
+            if !r.insert(item) {
+              return Err(result::Error::DuplicateSetValue { index: i });
+            }
+          }
+          Ok(StrictSet(r))
+        }
+      }
+
+      impl<T, T2> S2ProtoUnpackRef<Vec<T>> for StrictSet<$set<T2>>
+      where
+        T2: S2ProtoUnpackRef<T> $(+ $bound)+,
+      {
+        fn unpack_ref(value: &Vec<T>) -> Result<StrictSet<$set<T2>>> {
+          let mut r = $set::new();
+          for (i, elem) in value.iter().enumerate() {
+            let item = T2::unpack_ref(elem).map_err(|e| result::Error::ListElement {
+              source: Box::new(e),
+              index: i,
+            })?;
+            if !r.insert(item) {
+              return Err(result::Error::DuplicateSetValue { index: i });
+            }
+          }
+          Ok(StrictSet(r))
+        }
+      }
+    )*
+  }
+}
+
+impl_set! {
+  HashSet: Eq, Hash;
+  BTreeSet: Ord
+}
+
+impl_option!([T, T2] HashSet<T2> => Vec<T>);
+impl_option!([T, T2] BTreeSet<T2> => Vec<T>);
+impl_option!([S, T] StrictSet<S> => Vec<T>);
+
+// map
+
+// Keys convert like values, their errors are reported as `MapKey`
+
+impl<K, V, K2, V2> S2ProtoPack<HashMap<K, V>> for HashMap<K2, V2>
+where
+  K: Eq + Hash,
+  K2: S2ProtoPack<K> + Eq + Hash,
+  V2: S2ProtoPack<V>,
+{
+  fn pack(self) -> Result<HashMap<K, V>> {
+    let mut r = HashMap::with_capacity(self.len());
+    for (k, v) in self.into_iter() {
+      let k2 = k.pack().map_err(|e| result::Error::MapKey {
+        source: Box::new(e),
+      })?;
+      let v2 = v.pack().map_err(|e| result::Error::MapEntry {
+        source: Box::new(e),
+      })?;
+      r.insert(k2, v2);
+    }
+    Ok(r)
+  }
+}
+
+impl<K, V, K2, V2> S2ProtoPackRef<HashMap<K, V>> for HashMap<K2, V2>
+where
+  K: Eq + Hash,
+  K2: S2ProtoPackRef<K> + Eq + Hash,
+  V2: S2ProtoPackRef<V>,
+{
+  fn pack_ref(&self) -> Result<HashMap<K, V>> {
+    let mut r = HashMap::with_capacity(self.len());
+    for (k, v) in self.iter() {
+      let k2 = k.pack_ref().map_err(|e| result::Error::MapKey {
+        source: Box::new(e),
+      })?;
+      let v2 = v.pack_ref().map_err(|e| result::Error::MapEntry {
+        source: Box::new(e),
+      })?;
+      r.insert(k2, v2);
+    }
+    Ok(r)
+  }
+}
+
+impl<K, V, K2, V2> S2ProtoUnpack<HashMap<K, V>> for HashMap<K2, V2>
+where
+  K: Eq + Hash,
+  K2: S2ProtoUnpack<K> + Eq + Hash,
+  V2: S2ProtoUnpack<V>,
+{
+  fn unpack(value: HashMap<K, V>) -> Result<HashMap<K2, V2>> {
+    let mut r = HashMap::with_capacity(value.len());
+    for (k, v) in value.into_iter() {
+      let k2 = K2::unpack(k).map_err(|e| result::Error::MapKey {
+        source: Box::new(e),
+      })?;
+      let v2 = V2::unpack(v).map_err(|e| result::Error::MapEntry {
+        source: Box::new(e),
+      })?;
+      r.insert(k2, v2);
+    }
+    Ok(r)
+  }
+}
+
+impl<K, V, K2, V2> S2ProtoUnpackRef<HashMap<K, V>> for HashMap<K2, V2>
+where
+  K: Eq + Hash,
+  K2: S2ProtoUnpackRef<K> + Eq + Hash,
+  V2: S2ProtoUnpackRef<V>,
+{
+  fn unpack_ref(value: &HashMap<K, V>) -> Result<HashMap<K2, V2>> {
+    let mut r = HashMap::with_capacity(value.len());
+    for (k, v) in value.iter() {
+      let k2 = K2::unpack_ref(k).map_err(|e| result::Error::MapKey {
+        source: Box::new(e),
+      })?;
+      let v2 = V2::unpack_ref(v).map_err(|e| result::Error::MapEntry {
+        source: Box::new(e),
+      })?;
+      r.insert(k2, v2);
+    }
+    Ok(r)
+  }
+}
+
+impl_option!([K, V, K2, V2] HashMap<K2, V2> => HashMap<K, V>);
+
+// tuple
+
+// Elements map by position: element `N` of the Rust tuple packs to element `N`
+// of the proto tuple, errors are reported at path `N`
+macro_rules! impl_tuple {
+  (
+    $(($($idx:tt: $rust:ident => $proto:ident),+))*
+  ) => {
+    $(
+      impl<$($rust, $proto),+> S2ProtoPack<($($proto,)+)> for ($($rust,)+)
+      where
+        $($rust: S2ProtoPack<$proto>),+
+      {
+        fn pack(self) -> Result<($($proto,)+)> {
+          Ok(($(
+            self.$idx.pack().map_err(|err| err.at(stringify!($idx)))?,
+          )+))
+        }
+      }
+
+      impl<$($rust, $proto),+> S2ProtoPackRef<($($proto,)+)> for ($($rust,)+)
+      where
+        $($rust: S2ProtoPackRef<$proto>),+
+      {
+        fn pack_ref(&self) -> Result<($($proto,)+)> {
+          Ok(($(
+            self.$idx.pack_ref().map_err(|err| err.at(stringify!($idx)))?,
+          )+))
+        }
+      }
+
+      impl<$($rust, $proto),+> S2ProtoUnpack<($($proto,)+)> for ($($rust,)+)
+      where
+        $($rust: S2ProtoUnpack<$proto>),+
+      {
+        fn unpack(value: ($($proto,)+)) -> Result<($($rust,)+)> {
+          Ok(($(
+            $rust::unpack(value.$idx).map_err(|err| err.at(stringify!($idx)))?,
+          )+))
+        }
+      }
+
+      impl<$($rust, $proto),+> S2ProtoUnpackRef<($($proto,)+)> for ($($rust,)+)
+      where
+        $($rust: S2ProtoUnpackRef<$proto>),+
+      {
+        fn unpack_ref(value: &($($proto,)+)) -> Result<($($rust,)+)> {
+          Ok(($(
+            $rust::unpack_ref(&value.$idx).map_err(|err| err.at(stringify!($idx)))?,
+          )+))
+        }
+      }
+
+      impl_option!([$($rust, $proto),+] ($($rust,)+) => ($($proto,)+));
+    )*
+  }
+}
+
+impl_tuple! {
+  (0: A => PA, 1: B => PB)
+  (0: A => PA, 1: B => PB, 2: C => PC)
+  (0: A => PA, 1: B => PB, 2: C => PC, 3: D => PD)
+}
+
+// Fallible values: the error is converted and returned by `pack`
+
+impl<T, R, E> S2ProtoPack<T> for std::result::Result<R, E>
+where
+  R: S2ProtoPack<T>,
+  E: Into<result::Error>,
+{
+  fn pack(self) -> Result<T> {
+    self.map_err(Into::into)?.pack()
+  }
+}
+
+// Smart pointers
+
+// `Box<T>` is left out: being `#[fundamental]`, it would overlap with the
+// blanket enum impls below
+
+/// Shared pointers clone the inner value on `pack` unless they hold the only
+/// reference, `pack_ref` never clones
+macro_rules! impl_shared {
+  (
+    $($ptr:ident),*
+  ) => {
+    $(
+      impl<T, P> S2ProtoPack<P> for $ptr<T>
+      where
+        T: S2ProtoPack<P> + Clone,
+      {
+        fn pack(self) -> Result<P> {
+          $ptr::try_unwrap(self)
+            .unwrap_or_else(|shared| (*shared).clone())
+            .pack()
+        }
+      }
+
+      impl<T, P> S2ProtoPackRef<P> for $ptr<T>
+      where
+        T: S2ProtoPackRef<P>,
+      {
+        fn pack_ref(&self) -> Result<P> {
+          (**self).pack_ref()
+        }
+      }
+
+      impl<T, P> S2ProtoUnpack<P> for $ptr<T>
+      where
+        T: S2ProtoUnpack<P>,
+      {
+        fn unpack(value: P) -> Result<$ptr<T>> {
+          T::unpack(value).map($ptr::new)
+        }
+      }
+
+      impl<T, P> S2ProtoUnpackRef<P> for $ptr<T>
+      where
+        T: S2ProtoUnpackRef<P>,
+      {
+        fn unpack_ref(value: &P) -> Result<$ptr<T>> {
+          T::unpack_ref(value).map($ptr::new)
+        }
+      }
+    )*
+  }
+}
+
+impl_shared! {
+  Arc,
+  Rc
+}
+
+// Enum
+
+impl<T> S2ProtoPack<i32> for T
+where
+  T: S2ProtoEnum,
+  <T as S2ProtoEnum>::ProtoEnum: Into<i32>,
+{
+  fn pack(self) -> Result<i32> {
+    let v = <Self as S2ProtoEnum>::pack(&self);
+    Ok(v.into())
+  }
+}
+
+impl<T> S2ProtoPackRef<i32> for T
+where
+  T: S2ProtoEnum,
+  <T as S2ProtoEnum>::ProtoEnum: Into<i32>,
+{
+  fn pack_ref(&self) -> Result<i32> {
+    Ok(<Self as S2ProtoEnum>::pack(self).into())
+  }
+}
+
+impl<T> S2ProtoUnpack<i32> for T
+where
+  T: S2ProtoEnum,
+{
+  fn unpack(v: i32) -> Result<T> {
+    <Self as S2ProtoEnum>::from_i32(v).ok_or(result::Error::EnumDiscriminantNotFound {
+      enum_name: T::NAME,
+      discriminant: v,
+    })
+  }
+}
+
+impl<T> S2ProtoUnpackRef<i32> for T
+where
+  T: S2ProtoEnum,
+{
+  fn unpack_ref(v: &i32) -> Result<T> {
+    <T as S2ProtoUnpack<i32>>::unpack(*v)
   }
 }
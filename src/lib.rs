@@ -1,5 +1,9 @@
 mod convert;
 pub mod result;
+#[cfg(feature = "strict_floats")]
+mod strict_floats;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 use crate::result::Error;
 
@@ -7,9 +11,28 @@ use crate::result::Error;
 #[macro_use]
 extern crate s2_grpc_utils_derive;
 
-pub use self::convert::Json;
+#[cfg(feature = "base64")]
+pub use self::convert::Base64;
+#[cfg(feature = "cbor")]
+pub use self::convert::CborFormat;
+#[cfg(feature = "msgpack")]
+pub use self::convert::MsgpackFormat;
+#[cfg(feature = "chrono")]
+pub use self::convert::RfcTimestamp;
+pub use self::convert::{
+  pack_any, pack_any_into, pack_any_with_url, pack_iter, pack_proto_any, unpack_any,
+  unpack_any_accepting, unpack_any_limited, unpack_any_with_url, unpack_cow, unpack_iter,
+  unpack_proto_any, AnyRegistry, CanonicalJson, CanonicalJsonFormat, DurationMillis,
+  DurationSeconds, Encoded, ErasedMessage, ErasedPack, ErasedUnpack, FieldMask, Format, Json,
+  JsonFormat, JsonTypeUrl, JsonWithUrl, PackIter, ProtoName, StrictSet, UnpackIter, Unpacker,
+  JSON_TYPE_URL,
+};
 pub use s2_grpc_utils_derive::*;
 
+#[diagnostic::on_unimplemented(
+  message = "`{Self}` can not be packed into `{T}`",
+  label = "no `S2ProtoPack<{T}>` conversion for this type"
+)]
 pub trait S2ProtoPack<T>
 where
   Self: Sized,
@@ -17,6 +40,15 @@ where
   fn pack(self) -> Result<T, Error>;
 }
 
+/// Same as `S2ProtoPack` but packs from a reference, which avoids cloning borrowed values
+pub trait S2ProtoPackRef<T> {
+  fn pack_ref(&self) -> Result<T, Error>;
+}
+
+#[diagnostic::on_unimplemented(
+  message = "`{Self}` can not be unpacked from `{T}`",
+  label = "no `S2ProtoUnpack<{T}>` conversion for this type"
+)]
 pub trait S2ProtoUnpack<T>
 where
   Self: Sized,
@@ -24,6 +56,14 @@ where
   fn unpack(value: T) -> Result<Self, Error>;
 }
 
+/// Same as `S2ProtoUnpack` but unpacks from a reference, the proto value stays usable
+pub trait S2ProtoUnpackRef<T>
+where
+  Self: Sized,
+{
+  fn unpack_ref(value: &T) -> Result<Self, Error>;
+}
+
 pub trait S2ProtoEnum
 where
   Self: Sized,
@@ -49,6 +89,19 @@ where
   }
 }
 
+impl<T1, T2> S2ProtoPackRef<Option<T1>> for Option<T2>
+where
+  T2: S2ProtoPackRef<T1>,
+{
+  fn pack_ref(&self) -> Result<Option<T1>, Error> {
+    if let Some(value) = self {
+      Ok(Some(value.pack_ref()?))
+    } else {
+      Ok(None)
+    }
+  }
+}
+
 impl<T1, T2> S2ProtoUnpack<Option<T1>> for Option<T2>
 where
   T2: S2ProtoUnpack<T1>,
@@ -61,3 +114,16 @@ where
     }
   }
 }
+
+impl<T1, T2> S2ProtoUnpackRef<Option<T1>> for Option<T2>
+where
+  T2: S2ProtoUnpackRef<T1>,
+{
+  fn unpack_ref(value: &Option<T1>) -> Result<Self, Error> {
+    if let Some(value) = value {
+      Ok(Some(T2::unpack_ref(value)?))
+    } else {
+      Ok(None)
+    }
+  }
+}
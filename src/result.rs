@@ -5,24 +5,122 @@ use snafu::Snafu;
 pub enum Error {
   #[snafu(display("JSON error: {}", source))]
   Json { source: serde_json::Error },
+  #[snafu(display("JSON encode error: {}", source))]
+  JsonEncode { source: serde_json::Error },
   #[snafu(display("Could not convert json value from type_url: {}", type_url))]
   JsonTypeUrlUnknown { type_url: String },
+  #[snafu(display("Could not unpack Any of type_url {}, expected {}", type_url, expected))]
+  AnyTypeUrlMismatch { type_url: String, expected: String },
+  #[snafu(display("Could not decode Any of unregistered type_url: {}", type_url))]
+  AnyTypeUrlUnknown { type_url: String },
+  #[cfg(feature = "cbor")]
+  #[snafu(display("CBOR encode error: {}", source))]
+  CborEncode {
+    source: ciborium::ser::Error<std::io::Error>,
+  },
+  #[cfg(feature = "cbor")]
+  #[snafu(display("CBOR decode error: {}", source))]
+  CborDecode {
+    source: ciborium::de::Error<std::io::Error>,
+  },
+  #[cfg(feature = "msgpack")]
+  #[snafu(display("MessagePack encode error: {}", source))]
+  MsgpackEncode { source: rmp_serde::encode::Error },
+  #[cfg(feature = "msgpack")]
+  #[snafu(display("MessagePack decode error: {}", source))]
+  MsgpackDecode { source: rmp_serde::decode::Error },
+  #[snafu(display("Protobuf encode error: {}", source))]
+  ProtoEncode { source: prost::EncodeError },
+  #[snafu(display("Protobuf decode error: {}", source))]
+  ProtoDecode { source: prost::DecodeError },
   #[snafu(display("Could not unpack a non-optional value from null"))]
   ValueNotPresent,
   #[snafu(display("Could not unpack field '{}' from null", field_name))]
   FieldValueNotPresent { field_name: &'static str },
+  #[snafu(display("Could not pack non-finite float {} into JSON", value))]
+  NonFiniteFloat { value: f64 },
   #[snafu(display("JSON value nested too deeply"))]
   JsonValueNestedTooDeeply,
+  #[snafu(display("JSON payload is nested deeper than the limit of {}", limit))]
+  DepthLimitExceeded { limit: usize },
+  #[snafu(display("Could not pack a non-object JSON value into google.protobuf.Struct"))]
+  JsonValueNotObject,
+  #[snafu(display("Could not pack a non-array JSON value into google.protobuf.ListValue"))]
+  JsonValueNotArray,
+  #[snafu(display("at {}: {}", path, source))]
+  Field { path: String, source: Box<Error> },
   #[snafu(display("List element {}: {}", index, source))]
   ListElement { source: Box<Error>, index: usize },
   #[snafu(display("Map entry: {}", source))]
   MapEntry { source: Box<Error> },
+  #[snafu(display("Map key: {}", source))]
+  MapKey { source: Box<Error> },
+  #[snafu(display("Expected {} list elements, got {}", expected, actual))]
+  WrongLength { expected: usize, actual: usize },
+  #[snafu(display("Only one of the variant fields can be set, got: {}", fields.join(", ")))]
+  MultipleVariantFields { fields: Vec<&'static str> },
+  #[snafu(display("Duplicate set value at list element {}", index))]
+  DuplicateSetValue { index: usize },
+  #[cfg(feature = "base64")]
+  #[snafu(display("Base64 decode error: {}", source))]
+  Base64 { source: base64::DecodeError },
+  #[cfg(feature = "bigdecimal")]
   #[snafu(display("Parse decimal error: {}", source))]
   ParseBigDecimal {
     source: bigdecimal::ParseBigDecimalError,
   },
+  #[cfg(feature = "decimal")]
+  #[snafu(display("Parse rust_decimal error: {}", source))]
+  Decimal { source: rust_decimal::Error },
+  #[snafu(display("Parse address error: {}", source))]
+  AddrParse { source: std::net::AddrParseError },
+  #[snafu(display("Parse integer error: {}", source))]
+  ParseInt { source: std::num::ParseIntError },
+  #[snafu(display("Parse char error: {}", source))]
+  ParseChar { source: std::char::ParseCharError },
+  #[snafu(display("String is not valid UTF-8: {}", source))]
+  NonUtf8 { source: std::str::Utf8Error },
+  #[snafu(display("Path is not valid UTF-8: {}", path))]
+  NonUtf8Path { path: String },
+  #[cfg(feature = "uuid")]
+  #[snafu(display("Parse uuid error: {}", source))]
+  Uuid { source: uuid::Error },
+  #[cfg(feature = "uuid")]
+  #[snafu(display("Could not unpack uuid from {} bytes, expected 16", len))]
+  UuidBytesLength { len: usize },
   #[snafu(display("Parse duration error: {}", message))]
   ParseDuration { message: String },
+  #[snafu(display("Timestamp is out of range: seconds = {}, nanos = {}", seconds, nanos))]
+  InvalidTimestamp { seconds: i64, nanos: i32 },
+  #[cfg(feature = "chrono")]
+  #[snafu(display("Parse timestamp error: {}", source))]
+  TimestampParse { source: chrono::ParseError },
+  #[snafu(display("Time of day out of range: {} seconds since midnight", seconds))]
+  TimeOfDayOutOfRange { seconds: i32 },
+  #[snafu(display("SystemTime is out of the range of google.protobuf.Timestamp"))]
+  SystemTimeOutOfRange,
+  #[snafu(display("Duration seconds out of range: {}", seconds))]
+  DurationSecondsOutOfRange { seconds: u64 },
+  #[snafu(display("Duration milliseconds out of range: {}", millis))]
+  DurationMillisOutOfRange { millis: u128 },
+  #[snafu(display("Duration nanos out of range: {}", nanos))]
+  DurationNanosOutOfRange { nanos: i32 },
+  #[snafu(display(
+    "Could not unpack a negative duration: seconds = {}, nanos = {}",
+    seconds,
+    nanos
+  ))]
+  DurationNegative { seconds: i64, nanos: i32 },
+  #[snafu(display(
+    "Duration seconds and nanos must have the same sign: seconds = {}, nanos = {}",
+    seconds,
+    nanos
+  ))]
+  DurationSignMismatch { seconds: i64, nanos: i32 },
+  #[snafu(display("Integer value {} does not fit in {}", value, target))]
+  IntegerOverflow { value: i64, target: &'static str },
+  #[snafu(display("Could not unpack {} from 0", target))]
+  ZeroValue { target: &'static str },
   #[snafu(display(
     "Enum discriminant is not found: enum type = {}, discriminant = {}",
     enum_name,
@@ -32,6 +130,129 @@ pub enum Error {
     enum_name: &'static str,
     discriminant: i32,
   },
+  #[snafu(display("Validation failed: {}", message))]
+  Validation { message: String },
+  #[snafu(display("{}", display_multiple(errors)))]
+  Multiple { errors: Vec<Error> },
+}
+
+fn display_multiple(errors: &[Error]) -> String {
+  let messages: Vec<_> = errors.iter().map(|err| err.to_string()).collect();
+  messages.join("; ")
+}
+
+impl Error {
+  /// Prefixes the field path of this error with `field`,
+  /// e.g. `users[2].created_at` after calling `at("users")` on an element error
+  pub fn at(self, field: &str) -> Error {
+    if let Error::Multiple { errors } = self {
+      return Error::Multiple {
+        errors: errors.into_iter().map(|err| err.at(field)).collect(),
+      };
+    }
+    let (path, source) = self.into_path();
+    Error::Field {
+      path: format!("{}{}", field, path),
+      source: Box::new(source),
+    }
+  }
+
+  fn into_path(self) -> (String, Error) {
+    match self {
+      Error::Field { path, source } => (format!(".{}", path), *source),
+      Error::FieldValueNotPresent { field_name } => {
+        (format!(".{}", field_name), Error::ValueNotPresent)
+      }
+      Error::ListElement { source, index } => {
+        let (path, source) = source.into_path();
+        (format!("[{}]{}", index, path), source)
+      }
+      err => (String::new(), err),
+    }
+  }
+}
+
+#[cfg(feature = "tonic")]
+impl Error {
+  /// gRPC status code of this error: `Internal` for errors packing or serializing
+  /// our own values, `InvalidArgument` for malformed input
+  pub fn code(&self) -> tonic::Code {
+    use tonic::Code;
+
+    match self {
+      Error::Field { source, .. }
+      | Error::ListElement { source, .. }
+      | Error::MapEntry { source }
+      | Error::MapKey { source } => source.code(),
+      Error::Multiple { errors } => {
+        if errors.iter().any(|err| err.code() == Code::Internal) {
+          Code::Internal
+        } else {
+          Code::InvalidArgument
+        }
+      }
+      Error::JsonEncode { .. }
+      | Error::ProtoEncode { .. }
+      | Error::NonFiniteFloat { .. }
+      | Error::JsonValueNotObject
+      | Error::JsonValueNotArray
+      | Error::SystemTimeOutOfRange
+      | Error::DurationSecondsOutOfRange { .. }
+      | Error::DurationMillisOutOfRange { .. } => Code::Internal,
+      #[cfg(feature = "cbor")]
+      Error::CborEncode { .. } => Code::Internal,
+      #[cfg(feature = "msgpack")]
+      Error::MsgpackEncode { .. } => Code::Internal,
+      #[cfg(feature = "cbor")]
+      Error::CborDecode { .. } => Code::InvalidArgument,
+      #[cfg(feature = "msgpack")]
+      Error::MsgpackDecode { .. } => Code::InvalidArgument,
+      #[cfg(feature = "bigdecimal")]
+      Error::ParseBigDecimal { .. } => Code::InvalidArgument,
+      #[cfg(feature = "decimal")]
+      Error::Decimal { .. } => Code::InvalidArgument,
+      #[cfg(feature = "uuid")]
+      Error::Uuid { .. } | Error::UuidBytesLength { .. } => Code::InvalidArgument,
+      #[cfg(feature = "chrono")]
+      Error::TimestampParse { .. } => Code::InvalidArgument,
+      #[cfg(feature = "base64")]
+      Error::Base64 { .. } => Code::InvalidArgument,
+      Error::Json { .. }
+      | Error::JsonTypeUrlUnknown { .. }
+      | Error::AnyTypeUrlMismatch { .. }
+      | Error::AnyTypeUrlUnknown { .. }
+      | Error::ProtoDecode { .. }
+      | Error::ValueNotPresent
+      | Error::FieldValueNotPresent { .. }
+      | Error::JsonValueNestedTooDeeply
+      | Error::DepthLimitExceeded { .. }
+      | Error::WrongLength { .. }
+      | Error::MultipleVariantFields { .. }
+      | Error::DuplicateSetValue { .. }
+      | Error::AddrParse { .. }
+      | Error::ParseInt { .. }
+      | Error::ParseChar { .. }
+      | Error::NonUtf8 { .. }
+      | Error::NonUtf8Path { .. }
+      | Error::ParseDuration { .. }
+      | Error::InvalidTimestamp { .. }
+      | Error::TimeOfDayOutOfRange { .. }
+      | Error::DurationNanosOutOfRange { .. }
+      | Error::DurationNegative { .. }
+      | Error::DurationSignMismatch { .. }
+      | Error::IntegerOverflow { .. }
+      | Error::ZeroValue { .. }
+      | Error::EnumDiscriminantNotFound { .. }
+      | Error::Validation { .. } => Code::InvalidArgument,
+    }
+  }
+}
+
+#[cfg(feature = "tonic")]
+impl From<Error> for tonic::Status {
+  fn from(e: Error) -> tonic::Status {
+    tonic::Status::new(e.code(), e.to_string())
+  }
 }
 
 impl From<Error> for String {
@@ -0,0 +1,257 @@
+//! Walks a `Serialize` value looking for non-finite floats, which serde_json
+//! would otherwise silently write as `null`
+
+use serde::ser::{self, Serialize, Serializer};
+use std::fmt;
+
+use crate::result::{self, Result};
+
+pub(crate) fn check<T>(value: &T) -> Result<()>
+where
+  T: Serialize + ?Sized,
+{
+  match value.serialize(FloatCheck) {
+    Err(CheckError::NonFinite(value)) => Err(result::Error::NonFiniteFloat { value }),
+    // other errors are reported by serde_json
+    _ => Ok(()),
+  }
+}
+
+#[derive(Debug)]
+enum CheckError {
+  NonFinite(f64),
+  Custom,
+}
+
+impl fmt::Display for CheckError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      CheckError::NonFinite(value) => write!(f, "non-finite float: {}", value),
+      CheckError::Custom => write!(f, "serialize error"),
+    }
+  }
+}
+
+impl std::error::Error for CheckError {}
+
+impl ser::Error for CheckError {
+  fn custom<T: fmt::Display>(_msg: T) -> Self {
+    CheckError::Custom
+  }
+}
+
+struct FloatCheck;
+
+macro_rules! ignore {
+  ($($method:ident: $ty:ty),*) => {
+    $(
+      fn $method(self, _v: $ty) -> std::result::Result<(), CheckError> {
+        Ok(())
+      }
+    )*
+  };
+}
+
+impl Serializer for FloatCheck {
+  type Ok = ();
+  type Error = CheckError;
+  type SerializeSeq = Self;
+  type SerializeTuple = Self;
+  type SerializeTupleStruct = Self;
+  type SerializeTupleVariant = Self;
+  type SerializeMap = Self;
+  type SerializeStruct = Self;
+  type SerializeStructVariant = Self;
+
+  ignore! {
+    serialize_bool: bool,
+    serialize_i8: i8,
+    serialize_i16: i16,
+    serialize_i32: i32,
+    serialize_i64: i64,
+    serialize_u8: u8,
+    serialize_u16: u16,
+    serialize_u32: u32,
+    serialize_u64: u64,
+    serialize_char: char,
+    serialize_str: &str,
+    serialize_bytes: &[u8],
+    serialize_unit_struct: &'static str
+  }
+
+  fn serialize_f32(self, v: f32) -> std::result::Result<(), CheckError> {
+    self.serialize_f64(f64::from(v))
+  }
+
+  fn serialize_f64(self, v: f64) -> std::result::Result<(), CheckError> {
+    if v.is_finite() {
+      Ok(())
+    } else {
+      Err(CheckError::NonFinite(v))
+    }
+  }
+
+  fn serialize_none(self) -> std::result::Result<(), CheckError> {
+    Ok(())
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> std::result::Result<(), CheckError> {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> std::result::Result<(), CheckError> {
+    Ok(())
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    _variant: &'static str,
+  ) -> std::result::Result<(), CheckError> {
+    Ok(())
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    value: &T,
+  ) -> std::result::Result<(), CheckError> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _index: u32,
+    _variant: &'static str,
+    value: &T,
+  ) -> std::result::Result<(), CheckError> {
+    value.serialize(self)
+  }
+
+  fn serialize_seq(self, _len: Option<usize>) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+
+  fn serialize_tuple(self, _len: usize) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+
+  fn serialize_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> std::result::Result<Self, CheckError> {
+    Ok(self)
+  }
+}
+
+macro_rules! impl_compound {
+  ($($trait:ident: $method:ident),*) => {
+    $(
+      impl ser::$trait for FloatCheck {
+        type Ok = ();
+        type Error = CheckError;
+
+        fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> std::result::Result<(), CheckError> {
+          value.serialize(FloatCheck)
+        }
+
+        fn end(self) -> std::result::Result<(), CheckError> {
+          Ok(())
+        }
+      }
+    )*
+  };
+}
+
+impl_compound! {
+  SerializeSeq: serialize_element,
+  SerializeTuple: serialize_element,
+  SerializeTupleStruct: serialize_field,
+  SerializeTupleVariant: serialize_field
+}
+
+impl ser::SerializeMap for FloatCheck {
+  type Ok = ();
+  type Error = CheckError;
+
+  fn serialize_key<T: Serialize + ?Sized>(
+    &mut self,
+    key: &T,
+  ) -> std::result::Result<(), CheckError> {
+    key.serialize(FloatCheck)
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> std::result::Result<(), CheckError> {
+    value.serialize(FloatCheck)
+  }
+
+  fn end(self) -> std::result::Result<(), CheckError> {
+    Ok(())
+  }
+}
+
+macro_rules! impl_struct_compound {
+  ($($trait:ident),*) => {
+    $(
+      impl ser::$trait for FloatCheck {
+        type Ok = ();
+        type Error = CheckError;
+
+        fn serialize_field<T: Serialize + ?Sized>(
+          &mut self,
+          _key: &'static str,
+          value: &T,
+        ) -> std::result::Result<(), CheckError> {
+          value.serialize(FloatCheck)
+        }
+
+        fn end(self) -> std::result::Result<(), CheckError> {
+          Ok(())
+        }
+      }
+    )*
+  };
+}
+
+impl_struct_compound! {
+  SerializeStruct,
+  SerializeStructVariant
+}
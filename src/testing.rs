@@ -0,0 +1,29 @@
+//! Assertions for testing conversions of downstream types
+
+use std::fmt::Debug;
+
+use crate::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+
+/// Packs `value` into `P`, unpacks it back and asserts the result equals `value`,
+/// returns the packed value for further checks
+pub fn assert_round_trip<R, P>(value: R) -> P
+where
+  R: S2ProtoPack<P> + S2ProtoUnpack<P> + PartialEq + Clone + Debug,
+  P: Clone,
+{
+  let packed = value.clone().pack().expect("pack failed");
+  let unpacked = R::unpack(packed.clone()).expect("unpack failed");
+  assert_eq!(unpacked, value, "value changed after a round trip");
+  packed
+}
+
+/// Same as `assert_round_trip`, converting by reference
+pub fn assert_round_trip_ref<R, P>(value: &R) -> P
+where
+  R: S2ProtoPackRef<P> + S2ProtoUnpackRef<P> + PartialEq + Debug,
+{
+  let packed = value.pack_ref().expect("pack_ref failed");
+  let unpacked = R::unpack_ref(&packed).expect("unpack_ref failed");
+  assert_eq!(&unpacked, value, "value changed after a round trip");
+  packed
+}
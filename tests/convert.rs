@@ -0,0 +1,1390 @@
+#[cfg(feature = "bigdecimal")]
+use bigdecimal::BigDecimal;
+use prost_types::value::Kind;
+use prost_types::{Timestamp, Value};
+use s2_grpc_utils::{Json, S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[cfg(feature = "bigdecimal")]
+#[test]
+fn vec_empty() {
+  let packed: Vec<String> = Vec::<BigDecimal>::new().pack().unwrap();
+  assert!(packed.is_empty());
+
+  let unpacked = Vec::<BigDecimal>::unpack(Vec::<String>::new()).unwrap();
+  assert!(unpacked.is_empty());
+}
+
+#[cfg(feature = "bigdecimal")]
+#[test]
+fn vec_element_err() {
+  let values = vec!["1.5".to_string(), "x".to_string(), "2".to_string()];
+  let err = Vec::<BigDecimal>::unpack(values).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "List element 1: Parse decimal error: invalid digit found in string"
+  );
+}
+
+#[cfg(feature = "bigdecimal")]
+#[test]
+fn bigdecimal_precision() {
+  let digits = "12345678901234567890123456789.012345678901234567890";
+  let value: BigDecimal = digits.parse().unwrap();
+  let packed: String = value.pack_ref().unwrap();
+  assert_eq!(packed, digits);
+  assert_eq!(BigDecimal::unpack(packed).unwrap(), value);
+
+  let packed: Option<String> = value.clone().pack().unwrap();
+  assert_eq!(packed.as_deref(), Some(digits));
+  assert_eq!(
+    Option::<BigDecimal>::unpack(packed).unwrap(),
+    Some(value.clone())
+  );
+  assert_eq!(Option::<BigDecimal>::unpack(None::<String>).unwrap(), None);
+
+  // any `AsRef<str>` unpacks
+  assert_eq!(BigDecimal::unpack(digits).unwrap(), value);
+  assert_eq!(
+    BigDecimal::unpack(std::borrow::Cow::Borrowed(digits)).unwrap(),
+    value
+  );
+}
+
+#[test]
+fn array() {
+  let packed: Vec<i32> = [1, 2, 3].pack_ref().unwrap();
+  assert_eq!(packed, vec![1, 2, 3]);
+  assert_eq!(<[i32; 3]>::unpack(packed).unwrap(), [1, 2, 3]);
+
+  let err = <[i32; 3]>::unpack(vec![1, 2]).err().unwrap();
+  assert!(matches!(
+    err,
+    s2_grpc_utils::result::Error::WrongLength {
+      expected: 3,
+      actual: 2
+    }
+  ));
+  assert_eq!(format!("{}", err), "Expected 3 list elements, got 2");
+
+  let hash = [7_u8; 32];
+  let packed: Vec<u8> = hash.pack().unwrap();
+  assert_eq!(<[u8; 32]>::unpack(packed).unwrap(), hash);
+  assert!(<[u8; 32]>::unpack(vec![7_u8; 31]).is_err());
+}
+
+#[test]
+fn pack_result() {
+  use s2_grpc_utils::result::Error;
+
+  #[derive(Debug)]
+  struct DomainError(&'static str);
+
+  impl From<DomainError> for Error {
+    fn from(err: DomainError) -> Error {
+      Error::Validation {
+        message: err.0.to_string(),
+      }
+    }
+  }
+
+  let ok: Result<std::time::Duration, DomainError> = Ok(std::time::Duration::from_secs(1));
+  let packed: prost_types::Duration = ok.pack().unwrap();
+  assert_eq!(packed.seconds, 1);
+
+  let err: Result<std::time::Duration, DomainError> = Err(DomainError("no timeout"));
+  let err = S2ProtoPack::<prost_types::Duration>::pack(err)
+    .err()
+    .unwrap();
+  assert!(matches!(err, Error::Validation { ref message } if message == "no timeout"));
+
+  let values = vec![Ok(1), Err(DomainError("negative"))];
+  let err = S2ProtoPack::<Vec<i32>>::pack(values).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "List element 1: Validation failed: negative"
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn naive_time() {
+  use chrono::NaiveTime;
+
+  let midnight = NaiveTime::from_hms(0, 0, 0);
+  assert_eq!(S2ProtoPack::<i32>::pack(midnight).unwrap(), 0);
+  assert_eq!(NaiveTime::unpack(0).unwrap(), midnight);
+
+  let noon = NaiveTime::from_hms(12, 0, 0);
+  let packed: i32 = noon.pack_ref().unwrap();
+  assert_eq!(packed, 43200);
+  assert_eq!(NaiveTime::unpack(packed).unwrap(), noon);
+
+  let packed: Option<i32> = NaiveTime::from_hms_milli(23, 59, 59, 500).pack().unwrap();
+  assert_eq!(packed, Some(86399));
+  assert_eq!(
+    NaiveTime::unpack(packed).unwrap(),
+    NaiveTime::from_hms(23, 59, 59)
+  );
+
+  let err = NaiveTime::unpack(86400).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Time of day out of range: 86400 seconds since midnight"
+  );
+  assert!(NaiveTime::unpack(-1).is_err());
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn rfc_timestamp() {
+  use chrono::{TimeZone, Utc};
+  use s2_grpc_utils::RfcTimestamp;
+
+  let dt = Utc.ymd(2021, 3, 4).and_hms_milli(5, 6, 7, 890);
+  let packed: String = RfcTimestamp(dt).pack().unwrap();
+  assert_eq!(packed, "2021-03-04T05:06:07.890+00:00");
+  assert_eq!(RfcTimestamp::unpack(packed).unwrap(), RfcTimestamp(dt));
+
+  let packed: Option<String> = RfcTimestamp(dt).pack().unwrap();
+  assert_eq!(RfcTimestamp::unpack(packed).unwrap(), RfcTimestamp(dt));
+
+  let unpacked = RfcTimestamp::unpack_ref(&"2021-03-04T07:06:07.890+02:00".to_string()).unwrap();
+  assert_eq!(unpacked, RfcTimestamp(dt));
+
+  let err = RfcTimestamp::unpack("yesterday".to_string()).err().unwrap();
+  assert!(format!("{}", err).starts_with("Parse timestamp error: "));
+}
+
+#[cfg(feature = "prost-wkt")]
+#[test]
+fn prost_wkt_types() {
+  use s2_grpc_utils::S2ProtoUnpack;
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  let time = UNIX_EPOCH + Duration::new(1_600_000_000, 500);
+  let packed: prost_wkt_types::Timestamp = time.pack().unwrap();
+  assert_eq!(packed.seconds, 1_600_000_000);
+  assert_eq!(packed.nanos, 500);
+  assert_eq!(SystemTime::unpack(packed).unwrap(), time);
+
+  let packed: Option<prost_wkt_types::Duration> = Duration::from_millis(1500).pack_ref().unwrap();
+  assert_eq!(
+    Duration::unpack(packed).unwrap(),
+    Duration::from_millis(1500)
+  );
+  assert!(Duration::unpack(prost_wkt_types::Duration {
+    seconds: -1,
+    nanos: 0
+  })
+  .is_err());
+}
+
+#[cfg(all(feature = "prost-wkt", feature = "chrono"))]
+#[test]
+fn prost_wkt_types_derive() {
+  use chrono::{DateTime, TimeZone, Utc};
+  use s2_grpc_utils::S2ProtoUnpack;
+
+  #[derive(Debug, Clone)]
+  struct EventMessage {
+    created_at: Option<prost_wkt_types::Timestamp>,
+  }
+
+  #[derive(Debug, PartialEq, s2_grpc_utils::S2ProtoPack, s2_grpc_utils::S2ProtoUnpack)]
+  #[s2_grpc(message_type = "EventMessage")]
+  struct Event {
+    created_at: DateTime<Utc>,
+  }
+
+  let event = Event {
+    created_at: Utc.timestamp(1_600_000_000, 0),
+  };
+  let msg: EventMessage = event.pack().unwrap();
+  assert_eq!(msg.created_at.as_ref().unwrap().seconds, 1_600_000_000);
+  assert_eq!(
+    Event::unpack(msg).unwrap(),
+    Event {
+      created_at: Utc.timestamp(1_600_000_000, 0),
+    }
+  );
+}
+
+#[test]
+fn wrapping() {
+  use std::num::{Saturating, Wrapping};
+
+  let counter = Wrapping(u64::MAX) + Wrapping(2);
+  let packed: u64 = counter.pack_ref().unwrap();
+  assert_eq!(packed, 1);
+  assert_eq!(Wrapping::<u64>::unpack(packed).unwrap(), counter);
+
+  let packed: Option<u64> = Wrapping(7_u64).pack().unwrap();
+  assert_eq!(packed, Some(7));
+  assert_eq!(Wrapping::<u64>::unpack(packed).unwrap(), Wrapping(7));
+
+  let packed: u32 = (Saturating(u32::MAX) + Saturating(1)).pack().unwrap();
+  assert_eq!(packed, u32::MAX);
+  assert_eq!(
+    Saturating::<u32>::unpack(packed).unwrap(),
+    Saturating(u32::MAX)
+  );
+}
+
+#[test]
+fn vec_f64() {
+  let histogram = vec![0.5_f64, 1.25, -3.0, f64::MAX];
+  let packed: Vec<f64> = histogram.clone().pack().unwrap();
+  assert_eq!(packed, histogram);
+  assert_eq!(Vec::<f64>::unpack(packed.clone()).unwrap(), histogram);
+
+  let packed: Vec<f64> = histogram.pack_ref().unwrap();
+  assert_eq!(Vec::<f64>::unpack_ref(&packed).unwrap(), histogram);
+
+  let packed: Vec<f32> = vec![1.5_f32].pack().unwrap();
+  assert_eq!(packed, vec![1.5]);
+}
+
+#[test]
+fn vec_json() {
+  let values = vec![
+    Json((1_f64, "a".to_string())),
+    Json((2_f64, "b".to_string())),
+  ];
+  let packed: Vec<Value> = values.pack().unwrap();
+  assert_eq!(packed.len(), 2);
+  match packed[0].kind {
+    Some(Kind::ListValue(ref list)) => assert_eq!(list.values.len(), 2),
+    ref other => panic!("unexpected kind: {:?}", other),
+  }
+
+  let unpacked = Vec::<Json<(f64, String)>>::unpack(packed).unwrap();
+  let unpacked: Vec<_> = unpacked.into_iter().map(|v| v.0).collect();
+  assert_eq!(
+    unpacked,
+    vec![(1_f64, "a".to_string()), (2_f64, "b".to_string())]
+  );
+}
+
+#[test]
+fn std_duration() {
+  let duration = std::time::Duration::new(5, 250);
+  let packed: prost_types::Duration = duration.pack().unwrap();
+  assert_eq!(
+    packed,
+    prost_types::Duration {
+      seconds: 5,
+      nanos: 250
+    }
+  );
+  assert_eq!(std::time::Duration::unpack(packed).unwrap(), duration);
+
+  let packed: Option<prost_types::Duration> = Some(duration).pack().unwrap();
+  assert_eq!(
+    Option::<std::time::Duration>::unpack(packed).unwrap(),
+    Some(duration)
+  );
+}
+
+#[test]
+fn duration_millis_seconds() {
+  use s2_grpc_utils::{DurationMillis, DurationSeconds};
+  use std::time::Duration;
+
+  let packed: i64 = DurationMillis(Duration::from_micros(1500)).pack().unwrap();
+  assert_eq!(packed, 1);
+  assert_eq!(
+    DurationMillis::unpack(packed).unwrap(),
+    DurationMillis(Duration::from_millis(1))
+  );
+  let packed: i64 = DurationSeconds(Duration::from_millis(2500))
+    .pack_ref()
+    .unwrap();
+  assert_eq!(packed, 2);
+  assert_eq!(
+    DurationSeconds::unpack(packed).unwrap(),
+    DurationSeconds(Duration::from_secs(2))
+  );
+
+  let packed: Option<i64> = DurationMillis(Duration::from_millis(250)).pack().unwrap();
+  assert_eq!(packed, Some(250));
+  assert_eq!(
+    DurationMillis::unpack(packed).unwrap(),
+    DurationMillis(Duration::from_millis(250))
+  );
+
+  let err = S2ProtoPack::<i64>::pack(DurationMillis(Duration::from_secs(u64::MAX)))
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    format!(
+      "Duration milliseconds out of range: {}",
+      u64::MAX as u128 * 1000
+    )
+  );
+  let err = S2ProtoPack::<i64>::pack(DurationSeconds(Duration::from_secs(u64::MAX)))
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    format!("Duration seconds out of range: {}", u64::MAX)
+  );
+
+  let err = DurationMillis::unpack(-1500).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack a negative duration: seconds = -1, nanos = -500000000"
+  );
+  assert!(DurationSeconds::unpack(-1).is_err());
+}
+
+#[test]
+fn std_duration_err() {
+  let err = std::time::Duration::unpack(prost_types::Duration {
+    seconds: -1,
+    nanos: 0,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack a negative duration: seconds = -1, nanos = 0"
+  );
+
+  let err = std::time::Duration::unpack(prost_types::Duration {
+    seconds: 1,
+    nanos: 1_000_000_000,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Duration nanos out of range: 1000000000"
+  );
+
+  let err = std::time::Duration::new(u64::MAX, 0)
+    .pack()
+    .map(|_: prost_types::Duration| ())
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Duration seconds out of range: 18446744073709551615"
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn chrono_duration() {
+  let cases = vec![
+    (chrono::Duration::milliseconds(1500), 1, 500_000_000),
+    (chrono::Duration::milliseconds(-1500), -1, -500_000_000),
+    (chrono::Duration::nanoseconds(-5), 0, -5),
+    (chrono::Duration::seconds(-3), -3, 0),
+  ];
+  for (duration, seconds, nanos) in cases {
+    let packed: prost_types::Duration = duration.pack().unwrap();
+    assert_eq!(packed, prost_types::Duration { seconds, nanos });
+    assert_eq!(chrono::Duration::unpack(packed).unwrap(), duration);
+  }
+
+  let packed: Option<prost_types::Duration> = Some(chrono::Duration::seconds(-10)).pack().unwrap();
+  assert_eq!(
+    Option::<chrono::Duration>::unpack(packed).unwrap(),
+    Some(chrono::Duration::seconds(-10))
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn chrono_duration_err() {
+  let err = chrono::Duration::unpack(prost_types::Duration {
+    seconds: -1,
+    nanos: 5,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Duration seconds and nanos must have the same sign: seconds = -1, nanos = 5"
+  );
+
+  let err = chrono::Duration::unpack(prost_types::Duration {
+    seconds: i64::MAX,
+    nanos: 0,
+  })
+  .err()
+  .unwrap();
+  assert!(format!("{}", err).starts_with("Parse duration error"));
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn naive_date_time() {
+  let dt = chrono::NaiveDate::from_ymd(2019, 12, 1).and_hms_nano(8, 30, 0, 5);
+  let packed: Timestamp = dt.pack().unwrap();
+  assert_eq!(
+    packed,
+    Timestamp {
+      seconds: 1575189000,
+      nanos: 5
+    }
+  );
+  assert_eq!(chrono::NaiveDateTime::unpack(packed).unwrap(), dt);
+
+  let date = chrono::NaiveDate::from_ymd(2019, 12, 1);
+  let packed: Option<Timestamp> = Some(date).pack().unwrap();
+  assert_eq!(
+    packed,
+    Some(Timestamp {
+      seconds: 1575158400,
+      nanos: 0
+    })
+  );
+  assert_eq!(
+    Option::<chrono::NaiveDate>::unpack(packed).unwrap(),
+    Some(date)
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn naive_date_time_err() {
+  let err = chrono::NaiveDateTime::unpack(Timestamp {
+    seconds: i64::MAX,
+    nanos: 0,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Timestamp is out of range: seconds = 9223372036854775807, nanos = 0"
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn date_time_utc_err() {
+  let err = chrono::DateTime::<chrono::Utc>::unpack(Timestamp {
+    seconds: i64::MAX,
+    nanos: 0,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Timestamp is out of range: seconds = 9223372036854775807, nanos = 0"
+  );
+
+  let err = chrono::DateTime::<chrono::Utc>::unpack(Timestamp {
+    seconds: 0,
+    nanos: 1_000_000_000,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Timestamp is out of range: seconds = 0, nanos = 1000000000"
+  );
+}
+
+#[cfg(feature = "secrecy")]
+#[test]
+fn secret_string() {
+  use secrecy::{ExposeSecret, Secret};
+
+  let secret = Secret::new("hunter2".to_string());
+  let packed: String = secret.pack_ref().unwrap();
+  assert_eq!(packed, "hunter2");
+
+  let unpacked = Secret::<String>::unpack(packed).unwrap();
+  assert_eq!(unpacked.expose_secret(), "hunter2");
+  assert!(!format!("{:?}", unpacked).contains("hunter2"));
+
+  let packed: Option<String> = unpacked.pack().unwrap();
+  let unpacked = Secret::<String>::unpack(packed).unwrap();
+  assert_eq!(unpacked.expose_secret(), "hunter2");
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuid_string() {
+  let id = uuid::Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8").unwrap();
+  let packed: String = id.pack().unwrap();
+  assert_eq!(packed, "936da01f-9abd-4d9d-80c7-02af85c822a8");
+  assert_eq!(uuid::Uuid::unpack(packed).unwrap(), id);
+
+  let packed: Option<String> = Some(id).pack().unwrap();
+  assert_eq!(Option::<uuid::Uuid>::unpack(packed).unwrap(), Some(id));
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuid_string_err() {
+  let err = uuid::Uuid::unpack("not-a-uuid".to_string()).err().unwrap();
+  assert!(format!("{}", err).starts_with("Parse uuid error: "));
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuid_bytes() {
+  let id = uuid::Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8").unwrap();
+  let packed: Vec<u8> = id.pack().unwrap();
+  assert_eq!(packed.len(), 16);
+  assert_eq!(&packed[..], id.as_bytes());
+  assert_eq!(uuid::Uuid::unpack(packed).unwrap(), id);
+
+  let err = uuid::Uuid::unpack(vec![0_u8; 15]).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack uuid from 15 bytes, expected 16"
+  );
+}
+
+#[test]
+fn bytes_vec() {
+  for buf in [vec![], vec![1_u8, 2, 3]] {
+    let packed: Vec<u8> = buf.clone().pack().unwrap();
+    assert_eq!(packed, buf);
+    assert_eq!(Vec::<u8>::unpack(packed).unwrap(), buf);
+
+    let packed: Option<Vec<u8>> = Some(buf.clone()).pack().unwrap();
+    assert_eq!(Option::<Vec<u8>>::unpack(packed).unwrap(), Some(buf));
+  }
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_bytes() {
+  for buf in [vec![], vec![1_u8, 2, 3]] {
+    let bytes = bytes::Bytes::from(buf.clone());
+    let packed: Vec<u8> = bytes.clone().pack().unwrap();
+    assert_eq!(packed, buf);
+    assert_eq!(bytes::Bytes::unpack(packed).unwrap(), bytes);
+
+    let packed: bytes::Bytes = buf.clone().pack().unwrap();
+    assert_eq!(packed, bytes);
+    assert_eq!(Vec::<u8>::unpack(packed).unwrap(), buf);
+  }
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_string() {
+  let packed: bytes::Bytes = "héllo".to_string().pack().unwrap();
+  assert_eq!(packed, bytes::Bytes::from_static("héllo".as_bytes()));
+  assert_eq!(String::unpack(packed.clone()).unwrap(), "héllo");
+
+  let packed: bytes::Bytes = "héllo".to_string().pack_ref().unwrap();
+  assert_eq!(String::unpack_ref(&packed).unwrap(), "héllo");
+
+  let err = String::unpack(bytes::Bytes::from_static(&[0xff, 0xfe]))
+    .err()
+    .unwrap();
+  assert!(format!("{}", err).starts_with("String is not valid UTF-8: "));
+}
+
+#[test]
+fn narrow_int() {
+  let packed: i32 = (-5_i8).pack().unwrap();
+  assert_eq!(packed, -5);
+  assert_eq!(i8::unpack(packed).unwrap(), -5);
+
+  let packed: u32 = u16::MAX.pack().unwrap();
+  assert_eq!(packed, 65535);
+  assert_eq!(u16::unpack(packed).unwrap(), u16::MAX);
+
+  let packed: Option<u32> = Some(7_u8).pack().unwrap();
+  assert_eq!(Option::<u8>::unpack(packed).unwrap(), Some(7));
+
+  let err = u8::unpack(256_u32).err().unwrap();
+  assert_eq!(format!("{}", err), "Integer value 256 does not fit in u8");
+
+  let err = i16::unpack(-40000_i32).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Integer value -40000 does not fit in i16"
+  );
+}
+
+#[test]
+fn empty() {
+  let packed: () = ().pack().unwrap();
+  <()>::unpack(packed).unwrap();
+
+  let packed: Option<()> = ().pack().unwrap();
+  assert_eq!(packed, Some(()));
+  <()>::unpack(packed).unwrap();
+  assert!(<()>::unpack(None).is_err());
+}
+
+#[test]
+fn wrappers() {
+  fn round_trip<T>(value: T)
+  where
+    T: S2ProtoPack<Option<T>> + S2ProtoUnpack<Option<T>> + Clone + PartialEq + std::fmt::Debug,
+    Option<T>: S2ProtoPack<Option<T>> + S2ProtoUnpack<Option<T>>,
+  {
+    let packed: Option<T> = value.clone().pack().unwrap();
+    assert_eq!(packed, Some(value.clone()));
+    assert_eq!(T::unpack(packed).unwrap(), value);
+    assert!(T::unpack(None).is_err());
+
+    let packed: Option<T> = Some(value.clone()).pack().unwrap();
+    assert_eq!(Option::<T>::unpack(packed).unwrap(), Some(value));
+    let packed: Option<T> = None::<T>.pack().unwrap();
+    assert_eq!(Option::<T>::unpack(packed).unwrap(), None);
+  }
+
+  round_trip(1.5_f32);
+  round_trip(1.5_f64);
+  round_trip(-1_i64);
+  round_trip(1_u64);
+  round_trip(-1_i32);
+  round_trip(1_u32);
+  round_trip(true);
+  round_trip("text".to_string());
+  round_trip(vec![1_u8, 2]);
+}
+
+#[test]
+fn unpack_ref() {
+  let value = Some("text".to_string());
+  assert_eq!(String::unpack_ref(&value).unwrap(), "text");
+  assert_eq!(value, Some("text".to_string()));
+
+  let values = vec![1_i64, 2, 3];
+  assert_eq!(Vec::<i64>::unpack_ref(&values).unwrap(), values);
+
+  let err = i8::unpack_ref(&300).err().unwrap();
+  assert_eq!(format!("{}", err), "Integer value 300 does not fit in i8");
+}
+
+#[test]
+fn tuple() {
+  let pair: (i32, String) = (1, "a".to_string()).pack().unwrap();
+  assert_eq!(pair, (1, "a".to_string()));
+  let unpacked = <(i32, String)>::unpack(pair).unwrap();
+  assert_eq!(unpacked, (1, "a".to_string()));
+
+  let packed: Option<(i32, String)> = Some((2_i32, "b".to_string())).pack().unwrap();
+  assert_eq!(
+    Option::<(i32, String)>::unpack(packed).unwrap(),
+    Some((2, "b".to_string()))
+  );
+
+  let quad: (i32, u32, bool, String) = (1_i8, 2_u16, true, "c".to_string()).pack().unwrap();
+  assert_eq!(quad, (1, 2, true, "c".to_string()));
+
+  let err = <(i32, i8)>::unpack((1, 300)).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at 1: Integer value 300 does not fit in i8"
+  );
+}
+
+#[test]
+fn set() {
+  use s2_grpc_utils::StrictSet;
+  use std::collections::{BTreeSet, HashSet};
+
+  let set: BTreeSet<i8> = [3, 1, 2].iter().cloned().collect();
+  let packed: Vec<i32> = set.pack().unwrap();
+  assert_eq!(packed, vec![1, 2, 3]);
+  let unpacked = BTreeSet::<i8>::unpack(vec![2, 1, 2]).unwrap();
+  assert_eq!(unpacked.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+
+  let set: HashSet<String> = ["a".to_string(), "b".to_string()].iter().cloned().collect();
+  let mut packed: Vec<String> = set.clone().pack().unwrap();
+  packed.sort();
+  assert_eq!(packed, vec!["a".to_string(), "b".to_string()]);
+  assert_eq!(HashSet::<String>::unpack(packed).unwrap(), set);
+
+  let strict = StrictSet::<HashSet<i64>>::unpack(vec![1, 2]).unwrap();
+  assert_eq!(strict.0.len(), 2);
+  let err = StrictSet::<BTreeSet<i64>>::unpack(vec![1, 2, 1])
+    .err()
+    .unwrap();
+  assert_eq!(format!("{}", err), "Duplicate set value at list element 2");
+
+  let strict = StrictSet::<BTreeSet<i8>>::unpack_ref(&vec![3, 1]).unwrap();
+  let packed: Vec<i32> = strict.pack_ref().unwrap();
+  assert_eq!(packed, vec![1, 3]);
+  let err = StrictSet::<HashSet<i8>>::unpack_ref(&vec![1, 1_i32])
+    .err()
+    .unwrap();
+  assert_eq!(format!("{}", err), "Duplicate set value at list element 1");
+  let err = StrictSet::<BTreeSet<i8>>::unpack_ref(&vec![1, 1000_i32])
+    .err()
+    .unwrap();
+  assert!(format!("{}", err).starts_with("List element 1: "));
+}
+
+#[test]
+fn option_of_collection() {
+  use std::collections::HashMap;
+
+  let packed: Option<Vec<i32>> = vec![1_i8, 2].pack().unwrap();
+  assert_eq!(packed, Some(vec![1, 2]));
+  assert_eq!(Vec::<i8>::unpack(packed).unwrap(), vec![1, 2]);
+  let err = Vec::<i8>::unpack(None::<Vec<i32>>).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack a non-optional value from null"
+  );
+
+  let bytes: Option<Vec<u8>> = vec![1_u8, 2].pack().unwrap();
+  assert_eq!(bytes, Some(vec![1, 2]));
+
+  let mut map = HashMap::new();
+  map.insert("a".to_string(), 1_u16);
+  let packed: Option<HashMap<String, u32>> = map.pack_ref().unwrap();
+  assert_eq!(HashMap::<String, u16>::unpack_ref(&packed).unwrap(), map);
+
+  let pair: Option<(i32, String)> = (1_i32, "a".to_string()).pack().unwrap();
+  assert_eq!(pair, Some((1, "a".to_string())));
+}
+
+#[test]
+fn addr() {
+  use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+  let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+  let packed: String = ip.pack().unwrap();
+  assert_eq!(packed, "127.0.0.1");
+  assert_eq!(IpAddr::unpack(packed).unwrap(), ip);
+
+  let packed: Option<String> = Ipv6Addr::LOCALHOST.pack().unwrap();
+  assert_eq!(packed, Some("::1".to_string()));
+  assert_eq!(Ipv6Addr::unpack(packed).unwrap(), Ipv6Addr::LOCALHOST);
+
+  let addr: SocketAddr = "10.0.0.1:8080".parse().unwrap();
+  assert_eq!(
+    SocketAddr::unpack("10.0.0.1:8080".to_string()).unwrap(),
+    addr
+  );
+
+  let err = Ipv4Addr::unpack("::1".to_string()).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Parse address error: invalid IPv4 address syntax"
+  );
+}
+
+#[cfg(feature = "decimal")]
+#[test]
+fn decimal() {
+  use rust_decimal::Decimal;
+
+  let text = "12345678901234.567890123456789";
+  let value: Decimal = text.parse().unwrap();
+  let packed: String = value.pack().unwrap();
+  assert_eq!(packed, text);
+  let unpacked: Decimal = S2ProtoUnpack::unpack(packed).unwrap();
+  assert_eq!(unpacked, value);
+
+  let packed: Option<String> = Decimal::new(-15, 1).pack().unwrap();
+  assert_eq!(packed, Some("-1.5".to_string()));
+
+  let err = <Decimal as S2ProtoUnpack<String>>::unpack("1.2.3".to_string())
+    .err()
+    .unwrap();
+  assert!(format!("{}", err).starts_with("Parse rust_decimal error: "));
+}
+
+#[test]
+fn int128() {
+  let packed: String = u128::MAX.pack().unwrap();
+  assert_eq!(packed, "340282366920938463463374607431768211455");
+  assert_eq!(u128::unpack(packed).unwrap(), u128::MAX);
+
+  let packed: Option<String> = i128::MIN.pack().unwrap();
+  assert_eq!(
+    packed,
+    Some("-170141183460469231731687303715884105728".to_string())
+  );
+  assert_eq!(i128::unpack(packed).unwrap(), i128::MIN);
+
+  let err = u128::unpack("340282366920938463463374607431768211456".to_string())
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Parse integer error: number too large to fit in target type"
+  );
+  let err = u128::unpack("-1".to_string()).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Parse integer error: invalid digit found in string"
+  );
+}
+
+#[test]
+fn borrowed_str() {
+  use std::borrow::Cow;
+
+  let text = String::from("text");
+  let packed: String = Cow::Borrowed(text.as_str()).pack().unwrap();
+  assert_eq!(packed, text);
+  let packed: Option<String> = Cow::<str>::Owned("owned".to_string()).pack().unwrap();
+  assert_eq!(packed, Some("owned".to_string()));
+
+  let packed: String = "str".pack().unwrap();
+  assert_eq!(packed, "str");
+  let packed: Option<String> = Some("str").pack().unwrap();
+  assert_eq!(packed, Some("str".to_string()));
+  let packed: Vec<String> = vec!["a", "b"].pack().unwrap();
+  assert_eq!(packed, vec!["a".to_string(), "b".to_string()]);
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PointMessage {
+  #[prost(int32, tag = "1")]
+  x: i32,
+  #[prost(string, tag = "2")]
+  label: String,
+}
+
+impl s2_grpc_utils::ProtoName for PointMessage {
+  const PACKAGE: &'static str = "s2.test";
+  const NAME: &'static str = "Point";
+}
+
+#[test]
+fn proto_any() {
+  use s2_grpc_utils::{pack_proto_any, unpack_proto_any};
+
+  let point = PointMessage {
+    x: 3,
+    label: "p".to_string(),
+  };
+  let any = pack_proto_any(point.clone()).unwrap();
+  assert_eq!(any.type_url, "type.googleapis.com/s2.test.Point");
+  assert_eq!(
+    unpack_proto_any::<PointMessage>(any.clone()).unwrap(),
+    point
+  );
+
+  let err = unpack_proto_any::<PointMessage>(prost_types::Any {
+    type_url: "type.googleapis.com/s2.test.Other".to_string(),
+    value: any.value,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack Any of type_url type.googleapis.com/s2.test.Other, \
+     expected type.googleapis.com/s2.test.Point"
+  );
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct SizeMessage {
+  #[prost(uint32, tag = "1")]
+  width: u32,
+  #[prost(uint32, tag = "2")]
+  height: u32,
+}
+
+impl s2_grpc_utils::ProtoName for SizeMessage {
+  const PACKAGE: &'static str = "s2.test";
+  const NAME: &'static str = "Size";
+}
+
+#[test]
+fn any_registry() {
+  use s2_grpc_utils::{pack_proto_any, AnyRegistry};
+
+  let mut registry = AnyRegistry::new();
+  registry
+    .register::<PointMessage>()
+    .register::<SizeMessage>();
+  assert!(registry.contains("type.googleapis.com/s2.test.Size"));
+
+  let point = PointMessage {
+    x: 1,
+    label: "a".to_string(),
+  };
+  let size = SizeMessage {
+    width: 2,
+    height: 3,
+  };
+
+  let decoded = registry
+    .decode(&pack_proto_any(point.clone()).unwrap())
+    .unwrap();
+  assert_eq!(decoded.downcast_ref::<PointMessage>(), Some(&point));
+  assert!(decoded.downcast_ref::<SizeMessage>().is_none());
+
+  let decoded = registry
+    .decode(&pack_proto_any(size.clone()).unwrap())
+    .unwrap();
+  assert_eq!(decoded.downcast::<SizeMessage>(), Some(size));
+
+  let err = registry
+    .decode(&prost_types::Any {
+      type_url: "type.googleapis.com/s2.test.Other".to_string(),
+      value: vec![],
+    })
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not decode Any of unregistered type_url: type.googleapis.com/s2.test.Other"
+  );
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn time_crate() {
+  use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+  let dt = PrimitiveDateTime::new(
+    Date::from_calendar_date(2020, Month::May, 17).unwrap(),
+    Time::from_hms_nano(12, 30, 15, 500).unwrap(),
+  );
+  let packed: Timestamp = dt.pack().unwrap();
+  assert_eq!(
+    packed,
+    Timestamp {
+      seconds: 1589718615,
+      nanos: 500,
+    }
+  );
+  assert_eq!(PrimitiveDateTime::unpack(packed.clone()).unwrap(), dt);
+
+  let offset = dt
+    .assume_utc()
+    .to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
+  let packed_offset: Option<Timestamp> = offset.pack().unwrap();
+  assert_eq!(packed_offset, Some(packed.clone()));
+  let unpacked = OffsetDateTime::unpack(packed).unwrap();
+  assert_eq!(unpacked, offset);
+  assert_eq!(unpacked.offset(), UtcOffset::UTC);
+
+  let err = OffsetDateTime::unpack(Timestamp {
+    seconds: i64::MAX,
+    nanos: 0,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    format!(
+      "Timestamp is out of range: seconds = {}, nanos = 0",
+      i64::MAX
+    )
+  );
+  assert!(OffsetDateTime::unpack(Timestamp {
+    seconds: 0,
+    nanos: -1,
+  })
+  .is_err());
+}
+
+#[test]
+fn system_time() {
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  let time = UNIX_EPOCH + Duration::new(1589718615, 500);
+  let packed: Timestamp = time.pack().unwrap();
+  assert_eq!(
+    packed,
+    Timestamp {
+      seconds: 1589718615,
+      nanos: 500,
+    }
+  );
+  assert_eq!(SystemTime::unpack(packed).unwrap(), time);
+
+  let before_epoch = UNIX_EPOCH - Duration::new(1, 250_000_000);
+  let packed: Option<Timestamp> = before_epoch.pack().unwrap();
+  assert_eq!(
+    packed,
+    Some(Timestamp {
+      seconds: -2,
+      nanos: 750_000_000,
+    })
+  );
+  assert_eq!(SystemTime::unpack(packed).unwrap(), before_epoch);
+
+  let err = SystemTime::unpack(Timestamp {
+    seconds: 0,
+    nanos: 1_000_000_000,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Timestamp is out of range: seconds = 0, nanos = 1000000000"
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn offset_date_time() {
+  use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+
+  let utc = Utc.ymd(2020, 5, 17).and_hms_nano(12, 30, 15, 500);
+  let offset = utc.with_timezone(&FixedOffset::east(8 * 3600));
+  let packed: Timestamp = offset.pack().unwrap();
+  assert_eq!(packed, utc.pack().unwrap());
+
+  let unpacked = DateTime::<FixedOffset>::unpack(packed.clone()).unwrap();
+  assert_eq!(unpacked, offset);
+  assert_eq!(unpacked.offset().local_minus_utc(), 0);
+
+  let local = utc.with_timezone(&Local);
+  let packed_local: Option<Timestamp> = local.pack().unwrap();
+  assert_eq!(packed_local, Some(packed.clone()));
+  assert_eq!(DateTime::<Local>::unpack(packed).unwrap(), local);
+}
+
+#[test]
+fn char() {
+  let packed: String = 'é'.pack().unwrap();
+  assert_eq!(packed, "é");
+  assert_eq!(char::unpack(packed).unwrap(), 'é');
+  let packed: Option<String> = 'x'.pack().unwrap();
+  assert_eq!(char::unpack(packed).unwrap(), 'x');
+
+  let err = char::unpack(String::new()).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Parse char error: cannot parse char from empty string"
+  );
+  let err = char::unpack("ab".to_string()).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Parse char error: too many characters in string"
+  );
+}
+
+#[test]
+fn non_zero() {
+  use std::num::{NonZeroI32, NonZeroU64};
+
+  let value = NonZeroU64::new(42).unwrap();
+  let packed: u64 = value.pack().unwrap();
+  assert_eq!(packed, 42);
+  assert_eq!(NonZeroU64::unpack(packed).unwrap(), value);
+
+  let packed: Option<i32> = NonZeroI32::new(-1).unwrap().pack().unwrap();
+  assert_eq!(packed, Some(-1));
+
+  let err = NonZeroI32::unpack(0).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack std::num::NonZeroI32 from 0"
+  );
+  let err = NonZeroU64::unpack(Some(0_u64)).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack std::num::NonZeroU64 from 0"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct UserId(u64);
+
+#[derive(Debug, PartialEq, Clone)]
+struct UserName(String);
+
+s2_grpc_utils::s2_transparent! {
+  UserId => u64,
+  UserName => String,
+}
+
+#[test]
+fn transparent() {
+  let packed: u64 = UserId(1).pack().unwrap();
+  assert_eq!(packed, 1);
+  assert_eq!(UserId::unpack(1_u64).unwrap(), UserId(1));
+
+  let packed: Option<u64> = UserId(2).pack().unwrap();
+  assert_eq!(packed, Some(2));
+  let packed: Option<Option<u64>> = Some(UserId(3)).pack().unwrap();
+  assert_eq!(packed, Some(Some(3)));
+  assert_eq!(
+    Option::<UserId>::unpack(Some(3_u64)).unwrap(),
+    Some(UserId(3))
+  );
+  assert_eq!(Option::<UserId>::unpack(None::<u64>).unwrap(), None);
+  let err = UserId::unpack(None::<u64>).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack a non-optional value from null"
+  );
+
+  let name = UserName("a".to_string());
+  let packed: String = name.pack_ref().unwrap();
+  assert_eq!(UserName::unpack_ref(&packed).unwrap(), name);
+}
+
+#[cfg(feature = "tonic")]
+#[test]
+fn tonic_status() {
+  use s2_grpc_utils::result::Error;
+  use tonic::{Code, Status};
+
+  // tonic handlers return `Result<_, Status>`
+  #[allow(clippy::result_large_err)]
+  fn unpack_handler(value: Option<u64>) -> Result<u64, Status> {
+    Ok(u64::unpack(value)?)
+  }
+
+  let status = unpack_handler(None).err().unwrap();
+  assert_eq!(status.code(), Code::InvalidArgument);
+  assert_eq!(
+    status.message(),
+    "Could not unpack a non-optional value from null"
+  );
+
+  let status = Status::from(
+    Error::InvalidTimestamp {
+      seconds: 0,
+      nanos: -1,
+    }
+    .at("created_at"),
+  );
+  assert_eq!(status.code(), Code::InvalidArgument);
+  assert_eq!(
+    status.message(),
+    "at created_at: Timestamp is out of range: seconds = 0, nanos = -1"
+  );
+
+  let status = Status::from(Error::NonFiniteFloat { value: f64::NAN });
+  assert_eq!(status.code(), Code::Internal);
+
+  // malformed JSON from the client
+  let any = prost_types::Any {
+    type_url: s2_grpc_utils::JSON_TYPE_URL.to_string(),
+    value: b"{".to_vec(),
+  };
+  let err = s2_grpc_utils::unpack_any::<serde_json::Value>(any)
+    .err()
+    .unwrap();
+  assert_eq!(err.code(), Code::InvalidArgument);
+
+  // JSON object keys must be strings, so our own value fails to serialize
+  let map: std::collections::HashMap<(i32, i32), i32> = vec![((1, 2), 3)].into_iter().collect();
+  let err = s2_grpc_utils::pack_any(map).err().unwrap();
+  assert!(matches!(err, Error::JsonEncode { .. }));
+  assert_eq!(err.code(), Code::Internal);
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn base64_string() {
+  use s2_grpc_utils::Base64;
+
+  let packed: String = Base64(b"hello".to_vec()).pack().unwrap();
+  assert_eq!(packed, "aGVsbG8=");
+  assert_eq!(Base64::unpack(packed).unwrap(), Base64(b"hello".to_vec()));
+
+  let packed: Option<String> = Some(Base64(vec![0xff])).pack_ref().unwrap();
+  assert_eq!(packed, Some("/w==".to_string()));
+  assert_eq!(
+    Option::<Base64>::unpack(packed).unwrap(),
+    Some(Base64(vec![0xff]))
+  );
+
+  let err = Base64::unpack("not base64!".to_string()).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Base64 decode error: Invalid byte 32, offset 3."
+  );
+}
+
+#[test]
+fn field_mask() {
+  use s2_grpc_utils::FieldMask;
+
+  let mask = FieldMask(vec!["name".to_string(), "address.city".to_string()]);
+  assert!(mask.contains("name"));
+  assert!(mask.contains("address.city"));
+  assert!(mask.contains("address.city.code"));
+  assert!(!mask.contains("address"));
+  assert!(!mask.contains("address.zip"));
+  assert!(!mask.contains("names"));
+  assert!(!FieldMask::default().contains("name"));
+
+  let packed: prost_types::FieldMask = mask.pack_ref().unwrap();
+  assert_eq!(packed.paths, vec!["name", "address.city"]);
+  assert_eq!(FieldMask::unpack_ref(&packed).unwrap(), mask);
+
+  let packed: Option<prost_types::FieldMask> = mask.clone().pack().unwrap();
+  assert_eq!(Option::<FieldMask>::unpack(packed).unwrap(), Some(mask));
+  let err = FieldMask::unpack(None::<prost_types::FieldMask>)
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack a non-optional value from null"
+  );
+}
+
+#[test]
+fn bytes_cow() {
+  use std::borrow::Cow;
+
+  struct AttachmentMessage {
+    data: Vec<u8>,
+  }
+
+  let msg = AttachmentMessage {
+    data: vec![1, 2, 3],
+  };
+  let data = s2_grpc_utils::unpack_cow(&msg.data);
+  assert!(matches!(data, Cow::Borrowed(_)));
+  assert_eq!(data.as_ptr(), msg.data.as_ptr());
+  assert_eq!(&*data, &[1, 2, 3]);
+
+  let packed: Vec<u8> = data.pack_ref().unwrap();
+  assert_eq!(packed, vec![1, 2, 3]);
+
+  let ptr = msg.data.as_ptr();
+  let owned = Cow::<[u8]>::unpack(msg.data).unwrap();
+  assert!(matches!(owned, Cow::Owned(_)));
+  assert_eq!(owned.as_ptr(), ptr);
+  let packed: Option<Vec<u8>> = owned.pack().unwrap();
+  assert_eq!(packed, Some(vec![1, 2, 3]));
+}
+
+#[test]
+fn path_string() {
+  use std::ffi::OsString;
+  use std::path::PathBuf;
+
+  let path = PathBuf::from("/tmp/a.txt");
+  let packed: String = path.pack_ref().unwrap();
+  assert_eq!(packed, "/tmp/a.txt");
+  assert_eq!(PathBuf::unpack(packed).unwrap(), path);
+
+  let packed: Option<String> = Some(path.clone()).pack().unwrap();
+  assert_eq!(Option::<PathBuf>::unpack(packed).unwrap(), Some(path));
+
+  let name: String = OsString::from("a.txt").pack().unwrap();
+  assert_eq!(name, "a.txt");
+  assert_eq!(
+    OsString::unpack_ref(&name).unwrap(),
+    OsString::from("a.txt")
+  );
+}
+
+#[cfg(unix)]
+#[test]
+fn path_string_non_utf8() {
+  use std::ffi::OsStr;
+  use std::os::unix::ffi::OsStrExt;
+  use std::path::PathBuf;
+
+  let path = PathBuf::from(OsStr::from_bytes(b"/tmp/a\xff"));
+  let err = S2ProtoPackRef::<String>::pack_ref(&path).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Path is not valid UTF-8: /tmp/a\u{fffd}"
+  );
+  let err = S2ProtoPack::<String>::pack(path.into_os_string())
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Path is not valid UTF-8: /tmp/a\u{fffd}"
+  );
+}
+
+#[cfg(feature = "ordered-float")]
+#[test]
+fn ordered_float() {
+  use ordered_float::OrderedFloat;
+  use std::collections::BTreeSet;
+
+  let packed: f64 = OrderedFloat(1.5_f64).pack().unwrap();
+  assert_eq!(packed, 1.5);
+  assert_eq!(
+    OrderedFloat::<f64>::unpack(packed).unwrap(),
+    OrderedFloat(1.5)
+  );
+  let packed: f32 = OrderedFloat(-0.25_f32).pack_ref().unwrap();
+  assert_eq!(packed, -0.25);
+  assert_eq!(
+    OrderedFloat::<f32>::unpack_ref(&packed).unwrap(),
+    OrderedFloat(-0.25)
+  );
+
+  let packed: Option<f64> = OrderedFloat(2.0_f64).pack().unwrap();
+  assert_eq!(packed, Some(2.0));
+  assert!(OrderedFloat::<f64>::unpack(None::<f64>).is_err());
+
+  let unpacked = BTreeSet::<OrderedFloat<f64>>::unpack(vec![3.0, 1.0, 2.0]).unwrap();
+  assert_eq!(
+    unpacked.into_iter().collect::<Vec<_>>(),
+    vec![OrderedFloat(1.0), OrderedFloat(2.0), OrderedFloat(3.0)]
+  );
+}
+
+#[cfg(feature = "half")]
+#[test]
+fn half_float() {
+  use half::f16;
+
+  let packed: f32 = f16::from_f32(1.5).pack().unwrap();
+  assert_eq!(packed, 1.5);
+  assert_eq!(f16::unpack(packed).unwrap(), f16::from_f32(1.5));
+
+  // narrowing keeps about 3 significant decimal digits
+  let unpacked = f16::unpack(0.1_f32).unwrap();
+  assert!((unpacked.to_f32() - 0.1).abs() < 1e-3);
+  assert_ne!(unpacked.to_f32(), 0.1);
+  assert!(f16::unpack(1e6_f32).unwrap().is_infinite());
+
+  let packed: Option<f32> = Some(f16::from_f32(2.0)).pack_ref().unwrap();
+  assert_eq!(packed, Some(2.0));
+  assert_eq!(
+    Option::<f16>::unpack(packed).unwrap(),
+    Some(f16::from_f32(2.0))
+  );
+}
+
+#[test]
+fn unpack_iter_lazy() {
+  use s2_grpc_utils::unpack_iter;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  static UNPACKED: AtomicUsize = AtomicUsize::new(0);
+
+  #[derive(Debug)]
+  struct Counted(i32);
+
+  impl S2ProtoUnpack<i32> for Counted {
+    fn unpack(value: i32) -> s2_grpc_utils::result::Result<Counted> {
+      UNPACKED.fetch_add(1, Ordering::SeqCst);
+      if value < 0 {
+        return Err(s2_grpc_utils::result::Error::ValueNotPresent);
+      }
+      Ok(Counted(value))
+    }
+  }
+
+  let mut values: Vec<i32> = (0..10_000).collect();
+  values[2] = -1;
+
+  let mut iter = unpack_iter::<_, _, Counted>(values);
+  assert_eq!(iter.size_hint(), (10_000, Some(10_000)));
+  assert_eq!(UNPACKED.load(Ordering::SeqCst), 0);
+  assert_eq!(iter.next().unwrap().unwrap().0, 0);
+  assert_eq!(iter.next().unwrap().unwrap().0, 1);
+  assert_eq!(UNPACKED.load(Ordering::SeqCst), 2);
+
+  let err = iter.next().unwrap().err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "List element 2: Could not unpack a non-optional value from null"
+  );
+  assert_eq!(iter.next().unwrap().unwrap().0, 3);
+
+  let rest: s2_grpc_utils::result::Result<Vec<_>> = iter.take(100).collect();
+  assert_eq!(rest.unwrap().len(), 100);
+  assert_eq!(UNPACKED.load(Ordering::SeqCst), 104);
+}
@@ -1,6 +1,6 @@
 use prost_types::value::Kind;
 use prost_types::{Struct, Value};
-use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
 use serde_json::{json, Value as JsonValue};
 use std::collections::HashMap;
 
@@ -24,7 +24,7 @@ fn map_i32(v: i32) -> i32 {
 }
 
 #[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
-#[s2_grpc(message_type = "Message")]
+#[s2_grpc(message_type = "Message", pack_ref, unpack_ref)]
 struct Model {
   #[s2_grpc(map_fn = "map_i32")]
   v1: i32,
@@ -36,8 +36,8 @@ struct Model {
   map: HashMap<i32, NestedModel>,
 }
 
-#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
-#[s2_grpc(message_type = "NestedMessage")]
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq, Clone)]
+#[s2_grpc(message_type = "NestedMessage", pack_ref, unpack_ref)]
 struct NestedModel {
   v: i32,
 }
@@ -47,6 +47,7 @@ fn derive_enum() {
   use crate::S2ProtoEnum;
 
   #[derive(Debug, PartialEq)]
+  #[allow(clippy::upper_case_acronyms)]
   enum EnumProto {
     A = 0,
     BBBB = 1,
@@ -141,3 +142,814 @@ fn derive_err() {
     "Could not unpack field 'json' from null"
   )
 }
+
+#[test]
+fn derive_map() {
+  let mut by_name = HashMap::new();
+  by_name.insert("a".to_string(), NestedModel { v: 1 });
+  let packed: HashMap<String, NestedMessage> = by_name.pack().unwrap();
+  assert_eq!(packed.get("a"), Some(&NestedMessage { v: 1 }));
+  let unpacked = HashMap::<String, NestedModel>::unpack(packed).unwrap();
+  assert_eq!(unpacked.get("a"), Some(&NestedModel { v: 1 }));
+
+  let mut by_id = HashMap::new();
+  by_id.insert(42_i64, NestedModel { v: 2 });
+  let packed: HashMap<i64, NestedMessage> = by_id.pack().unwrap();
+  assert_eq!(packed.get(&42), Some(&NestedMessage { v: 2 }));
+  let unpacked = HashMap::<i64, NestedModel>::unpack(packed).unwrap();
+  assert_eq!(unpacked.get(&42), Some(&NestedModel { v: 2 }));
+}
+
+#[test]
+fn derive_map_err() {
+  let mut map = HashMap::new();
+  map.insert("a".to_string(), None);
+  let err = HashMap::<String, NestedModel>::unpack(map).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Map entry: Could not unpack a non-optional value from null"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct UserMessage {
+  user_id: i64,
+}
+
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "UserMessage")]
+struct User {
+  #[s2_grpc(proto_name = "user_id")]
+  id: i64,
+}
+
+#[test]
+fn derive_proto_name() {
+  let msg: UserMessage = User { id: 1 }.pack().unwrap();
+  assert_eq!(msg, UserMessage { user_id: 1 });
+  assert_eq!(User::unpack(msg).unwrap(), User { id: 1 });
+}
+
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "UserMessage")]
+struct CachedUser {
+  #[s2_grpc(proto_name = "user_id")]
+  id: i64,
+  #[s2_grpc(skip)]
+  cached_name: Option<String>,
+  #[s2_grpc(skip, default = "vec![0]")]
+  cached_ids: Vec<i64>,
+}
+
+#[test]
+fn derive_skip() {
+  let user = CachedUser {
+    id: 1,
+    cached_name: Some("name".to_string()),
+    cached_ids: vec![1, 2],
+  };
+  let msg: UserMessage = user.pack().unwrap();
+  assert_eq!(msg, UserMessage { user_id: 1 });
+  assert_eq!(
+    CachedUser::unpack(msg).unwrap(),
+    CachedUser {
+      id: 1,
+      cached_name: None,
+      cached_ids: vec![0],
+    }
+  );
+}
+
+mod shape_message {
+  #[derive(Debug, PartialEq, Clone)]
+  pub enum Kind {
+    Radius(f64),
+    Label(String),
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct ShapeMessage {
+  kind: Option<shape_message::Kind>,
+}
+
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "shape_message::Kind", pack_ref, unpack_ref)]
+enum ShapeKind {
+  Radius(f64),
+  #[s2_grpc(rename = "Label")]
+  Text(String),
+}
+
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "ShapeMessage")]
+struct Shape {
+  kind: ShapeKind,
+}
+
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "ShapeMessage")]
+struct OptionalShape {
+  kind: Option<ShapeKind>,
+}
+
+#[test]
+fn derive_oneof() {
+  let msg: ShapeMessage = Shape {
+    kind: ShapeKind::Text("a".to_string()),
+  }
+  .pack()
+  .unwrap();
+  assert_eq!(
+    msg,
+    ShapeMessage {
+      kind: Some(shape_message::Kind::Label("a".to_string()))
+    }
+  );
+  assert_eq!(
+    Shape::unpack(msg).unwrap(),
+    Shape {
+      kind: ShapeKind::Text("a".to_string())
+    }
+  );
+
+  let msg = ShapeMessage {
+    kind: Some(shape_message::Kind::Radius(1.5)),
+  };
+  assert_eq!(
+    Shape::unpack(msg).unwrap(),
+    Shape {
+      kind: ShapeKind::Radius(1.5)
+    }
+  );
+
+  let err = Shape::unpack(ShapeMessage { kind: None }).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'kind' from null"
+  );
+  assert_eq!(
+    OptionalShape::unpack(ShapeMessage { kind: None }).unwrap(),
+    OptionalShape { kind: None }
+  );
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum StatusProto {
+  Active = 0,
+  Deleted = 1,
+}
+
+impl StatusProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(StatusProto::Active),
+      1 => Some(StatusProto::Deleted),
+      _ => None,
+    }
+  }
+}
+
+impl From<StatusProto> for i32 {
+  fn from(v: StatusProto) -> i32 {
+    v as i32
+  }
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq, Eq, Hash)]
+#[s2_grpc(proto_enum_type = "StatusProto")]
+enum Status {
+  Active,
+  Deleted,
+}
+
+#[test]
+fn derive_enum_i32() {
+  let packed: i32 = Status::Deleted.pack().unwrap();
+  assert_eq!(packed, 1);
+  assert_eq!(
+    <Status as S2ProtoUnpack<i32>>::unpack(1).unwrap(),
+    Status::Deleted
+  );
+  assert_eq!(
+    <Status as S2ProtoUnpack<i32>>::unpack(0).unwrap(),
+    Status::Active
+  );
+
+  let err = <Status as S2ProtoUnpack<i32>>::unpack(7).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Enum discriminant is not found: enum type = Status, discriminant = 7"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum PriorityProto {
+  Unspecified = 0,
+  Low = 1,
+  High = 2,
+}
+
+impl PriorityProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(PriorityProto::Unspecified),
+      1 => Some(PriorityProto::Low),
+      2 => Some(PriorityProto::High),
+      _ => None,
+    }
+  }
+}
+
+impl From<PriorityProto> for i32 {
+  fn from(v: PriorityProto) -> i32 {
+    v as i32
+  }
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq, Clone, Copy)]
+#[s2_grpc(proto_enum_type = "PriorityProto")]
+enum Priority {
+  Unspecified,
+  Low,
+  High,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct TicketMessage {
+  priority: i32,
+}
+
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "TicketMessage", pack_ref, unpack_ref)]
+struct Ticket {
+  #[s2_grpc(enum, none_on_unspecified)]
+  priority: Option<Priority>,
+}
+
+#[test]
+fn derive_none_on_unspecified() {
+  let none = Ticket { priority: None };
+  let packed: TicketMessage = none.pack_ref().unwrap();
+  assert_eq!(packed, TicketMessage { priority: 0 });
+  assert_eq!(Ticket::unpack_ref(&packed).unwrap(), none);
+  assert_eq!(Ticket::unpack(packed).unwrap(), none);
+
+  let high = Ticket {
+    priority: Some(Priority::High),
+  };
+  let packed: TicketMessage = high.pack_ref().unwrap();
+  assert_eq!(packed, TicketMessage { priority: 2 });
+  assert_eq!(Ticket::unpack(packed).unwrap(), high);
+
+  // the unspecified variant itself packs to zero, which unpacks as `None`
+  let unspecified = Ticket {
+    priority: Some(Priority::Unspecified),
+  };
+  let packed: TicketMessage = unspecified.pack().unwrap();
+  assert_eq!(packed, TicketMessage { priority: 0 });
+  assert_eq!(Ticket::unpack_ref(&packed).unwrap(), none);
+
+  let err = Ticket::unpack(TicketMessage { priority: 7 }).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at priority: Enum discriminant is not found: enum type = Priority, discriminant = 7"
+  );
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, PartialEq, Clone)]
+struct MemberMessage {
+  created_at: Option<prost_types::Timestamp>,
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, PartialEq, Clone)]
+struct OrgMessage {
+  owner: Option<MemberMessage>,
+  users: Vec<MemberMessage>,
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "MemberMessage")]
+struct Member {
+  created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, S2ProtoPack, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "OrgMessage")]
+struct Org {
+  owner: Member,
+  users: Vec<Member>,
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn derive_err_path() {
+  let member = MemberMessage {
+    created_at: Some(prost_types::Timestamp {
+      seconds: 0,
+      nanos: 0,
+    }),
+  };
+  let missing = MemberMessage { created_at: None };
+
+  let msg = OrgMessage {
+    owner: Some(member.clone()),
+    users: vec![member.clone(), member.clone(), missing.clone()],
+  };
+  let err = Org::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at users[2].created_at: Could not unpack a non-optional value from null"
+  );
+
+  let msg = OrgMessage {
+    owner: Some(missing),
+    users: vec![],
+  };
+  let err = Org::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at owner.created_at: Could not unpack a non-optional value from null"
+  );
+
+  let msg = OrgMessage {
+    owner: None,
+    users: vec![member],
+  };
+  let err = Org::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'owner' from null"
+  );
+}
+
+#[test]
+fn derive_pack_ref() {
+  let mut map = HashMap::new();
+  map.insert(1, NestedModel { v: 2 });
+  let model = Model {
+    v1: 1,
+    v2: "text".to_string(),
+    json: json!({ "v": 1_f64 }),
+    json_optional: None,
+    elements: vec![NestedModel { v: 111 }],
+    map,
+  };
+
+  let msg: Message = model.pack_ref().unwrap();
+  let msg_optional: Option<Message> = model.pack_ref().unwrap();
+  assert_eq!(Some(msg.clone()), msg_optional);
+  assert_eq!(msg, model.pack().unwrap());
+
+  let kind = ShapeKind::Radius(1.5);
+  let packed: shape_message::Kind = kind.pack_ref().unwrap();
+  assert_eq!(packed, shape_message::Kind::Radius(1.5));
+  assert_eq!(kind, ShapeKind::Radius(1.5));
+}
+
+#[test]
+fn derive_unpack_ref() {
+  let mut map = HashMap::new();
+  map.insert(1, NestedMessage { v: 2 });
+  let msg = Message {
+    v1: 1,
+    _v2: "text".to_string(),
+    json: json!({ "v": 1_f64 }).pack().unwrap(),
+    json_optional: None,
+    elements: vec![NestedMessage { v: 111 }],
+    map,
+  };
+
+  let model = Model::unpack_ref(&msg).unwrap();
+  assert_eq!(model, Model::unpack(msg.clone()).unwrap());
+  assert_eq!(Model::unpack_ref(&Some(msg.clone())).unwrap(), model);
+  let packed: Message = model.pack_ref().unwrap();
+  assert_eq!(packed, msg);
+
+  let err = Model::unpack_ref(&Message { json: None, ..msg })
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'json' from null"
+  );
+
+  let kind = shape_message::Kind::Label("a".to_string());
+  assert_eq!(
+    ShapeKind::unpack_ref(&kind).unwrap(),
+    ShapeKind::Text("a".to_string())
+  );
+  assert_eq!(kind, shape_message::Kind::Label("a".to_string()));
+}
+
+#[test]
+fn derive_shared() {
+  use std::rc::Rc;
+  use std::sync::Arc;
+
+  let nested = Arc::new(NestedModel { v: 1 });
+  let msg: NestedMessage = nested.pack_ref().unwrap();
+  assert_eq!(msg, NestedMessage { v: 1 });
+  let shared = nested.clone();
+  let msg: NestedMessage = shared.pack().unwrap();
+  assert_eq!(msg, NestedMessage { v: 1 });
+  let msg: Option<NestedMessage> = nested.pack().unwrap();
+  assert_eq!(msg, Some(NestedMessage { v: 1 }));
+
+  let unpacked = Rc::<NestedModel>::unpack(NestedMessage { v: 2 }).unwrap();
+  assert_eq!(*unpacked, NestedModel { v: 2 });
+  let unpacked = Vec::<Arc<NestedModel>>::unpack(vec![NestedMessage { v: 3 }]).unwrap();
+  assert_eq!(*unpacked[0], NestedModel { v: 3 });
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, S2ProtoUnpack, PartialEq)]
+#[s2_grpc(message_type = "OrgMessage", collect_errors)]
+struct CheckedOrg {
+  owner: Member,
+  users: Vec<Member>,
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn derive_collect_errors() {
+  use s2_grpc_utils::result::Error;
+
+  let member = MemberMessage {
+    created_at: Some(prost_types::Timestamp {
+      seconds: 0,
+      nanos: 0,
+    }),
+  };
+  let missing = MemberMessage { created_at: None };
+
+  let msg = OrgMessage {
+    owner: None,
+    users: vec![member.clone(), missing],
+  };
+  let err = CheckedOrg::unpack(msg).err().unwrap();
+  match err {
+    Error::Multiple { ref errors } => assert_eq!(errors.len(), 2),
+    ref err => panic!("unexpected error: {}", err),
+  }
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'owner' from null; \
+     at users[1].created_at: Could not unpack a non-optional value from null"
+  );
+
+  let msg = OrgMessage {
+    owner: Some(member.clone()),
+    users: vec![member],
+  };
+  let org = CheckedOrg::unpack(msg).unwrap();
+  assert_eq!(org.users.len(), 1);
+}
+
+#[test]
+fn derive_vec_deque() {
+  use std::collections::VecDeque;
+
+  let mut queue = VecDeque::new();
+  queue.push_back(NestedModel { v: 2 });
+  queue.push_back(NestedModel { v: 3 });
+  queue.push_front(NestedModel { v: 1 });
+
+  let packed: Vec<NestedMessage> = queue.pack_ref().unwrap();
+  assert_eq!(
+    packed,
+    vec![
+      NestedMessage { v: 1 },
+      NestedMessage { v: 2 },
+      NestedMessage { v: 3 }
+    ]
+  );
+  let unpacked = VecDeque::<NestedModel>::unpack(packed).unwrap();
+  assert_eq!(unpacked.front(), Some(&NestedModel { v: 1 }));
+  assert_eq!(unpacked.back(), Some(&NestedModel { v: 3 }));
+  assert_eq!(unpacked, queue);
+
+  let packed: Vec<NestedMessage> = queue.pack().unwrap();
+  assert_eq!(packed[0], NestedMessage { v: 1 });
+}
+
+#[test]
+fn derive_enum_map_key() {
+  let mut map = HashMap::new();
+  map.insert(Status::Active, NestedModel { v: 1 });
+  map.insert(Status::Deleted, NestedModel { v: 2 });
+
+  let packed: HashMap<i32, NestedMessage> = map.pack_ref().unwrap();
+  assert_eq!(packed.get(&0), Some(&NestedMessage { v: 1 }));
+  assert_eq!(packed.get(&1), Some(&NestedMessage { v: 2 }));
+  assert_eq!(
+    HashMap::<Status, NestedModel>::unpack_ref(&packed).unwrap(),
+    map
+  );
+  assert_eq!(HashMap::<Status, NestedModel>::unpack(packed).unwrap(), map);
+
+  let mut packed = HashMap::new();
+  packed.insert(7, NestedMessage { v: 1 });
+  let err = HashMap::<Status, NestedModel>::unpack(packed)
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Map key: Enum discriminant is not found: enum type = Status, discriminant = 7"
+  );
+}
+
+#[test]
+fn derive_pack_iter() {
+  use s2_grpc_utils::{pack_iter, PackIter};
+
+  let packed: Vec<NestedMessage> = pack_iter((1..4).map(|v| NestedModel { v })).unwrap();
+  assert_eq!(
+    packed,
+    vec![
+      NestedMessage { v: 1 },
+      NestedMessage { v: 2 },
+      NestedMessage { v: 3 }
+    ]
+  );
+
+  let mut iter = PackIter::<_, NestedMessage>::new((1..3).map(|v| NestedModel { v }));
+  assert_eq!(iter.size_hint(), (2, Some(2)));
+  assert_eq!(iter.next().unwrap().unwrap(), NestedMessage { v: 1 });
+  assert_eq!(iter.next().unwrap().unwrap(), NestedMessage { v: 2 });
+  assert!(iter.next().is_none());
+
+  let durations = vec![
+    std::time::Duration::from_secs(1),
+    std::time::Duration::from_secs(u64::MAX),
+  ];
+  let err = pack_iter::<_, _, prost_types::Duration>(durations)
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "List element 1: Duration seconds out of range: 18446744073709551615"
+  );
+}
+
+#[test]
+fn derive_pack_slice() {
+  let models = [NestedModel { v: 1 }, NestedModel { v: 2 }];
+  let packed: Vec<NestedMessage> = models[..].pack().unwrap();
+  assert_eq!(packed, vec![NestedMessage { v: 1 }, NestedMessage { v: 2 }]);
+
+  let tail: &[NestedModel] = &models[1..];
+  let packed: Vec<NestedMessage> = tail.pack_ref().unwrap();
+  assert_eq!(packed, vec![NestedMessage { v: 2 }]);
+  assert_eq!(models.len(), 2);
+
+  let durations = [
+    std::time::Duration::from_secs(1),
+    std::time::Duration::from_secs(u64::MAX),
+  ];
+  let err = S2ProtoPack::<Vec<prost_types::Duration>>::pack(&durations[..])
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "List element 1: Duration seconds out of range: 18446744073709551615"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct StepMessage {
+  timeout: Option<prost_types::Duration>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct PipelineMessage {
+  steps: Vec<StepMessage>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "StepMessage", pack_ref)]
+struct Step {
+  timeout: std::time::Duration,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "PipelineMessage", pack_ref)]
+struct Pipeline {
+  steps: Vec<Step>,
+}
+
+#[test]
+fn derive_element_index() {
+  let step = |secs| Step {
+    timeout: std::time::Duration::from_secs(secs),
+  };
+  let pipeline = Pipeline {
+    steps: vec![step(1), step(2), step(u64::MAX)],
+  };
+  let err = S2ProtoPackRef::<PipelineMessage>::pack_ref(&pipeline)
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at steps[2].timeout: Duration seconds out of range: 18446744073709551615"
+  );
+
+  let timeout = |seconds| StepMessage {
+    timeout: Some(prost_types::Duration { seconds, nanos: 0 }),
+  };
+  let msg = PipelineMessage {
+    steps: vec![timeout(1), timeout(2), timeout(-3)],
+  };
+  let err = Pipeline::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at steps[2].timeout: Could not unpack a negative duration: seconds = -3, nanos = 0"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct QuotaMessage {
+  tenant: String,
+  limit: i32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoUnpack)]
+#[s2_grpc(message_type = "QuotaMessage", unpack_ref)]
+struct Quota {
+  #[s2_grpc(require_nonzero)]
+  tenant: String,
+  #[s2_grpc(require_nonzero, rename = "limit")]
+  max_requests: i32,
+}
+
+#[test]
+fn derive_require_nonzero() {
+  let quota = Quota::unpack(QuotaMessage {
+    tenant: "t".to_string(),
+    limit: 10,
+  })
+  .unwrap();
+  assert_eq!(quota.max_requests, 10);
+
+  let msg = QuotaMessage {
+    tenant: "t".to_string(),
+    limit: 0,
+  };
+  let err = Quota::unpack_ref(&msg).err().unwrap();
+  assert!(matches!(
+    err,
+    s2_grpc_utils::result::Error::FieldValueNotPresent {
+      field_name: "limit"
+    }
+  ));
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'limit' from null"
+  );
+  assert_eq!(
+    format!("{}", Quota::unpack(msg).err().unwrap()),
+    format!("{}", err)
+  );
+
+  let err = Quota::unpack(QuotaMessage {
+    tenant: String::new(),
+    limit: 10,
+  })
+  .err()
+  .unwrap();
+  assert!(matches!(
+    err,
+    s2_grpc_utils::result::Error::FieldValueNotPresent {
+      field_name: "tenant"
+    }
+  ));
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct AddressMessage {
+  city: String,
+  zip: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct CustomerMessage {
+  name: String,
+  address: Option<AddressMessage>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "CustomerMessage", pack_ref, unpack_ref)]
+struct Customer {
+  name: String,
+  #[s2_grpc(flatten = "address")]
+  city: String,
+  #[s2_grpc(flatten = "address", rename = "zip")]
+  zip_code: String,
+}
+
+#[test]
+fn derive_flatten() {
+  let customer = Customer {
+    name: "a".to_string(),
+    city: "b".to_string(),
+    zip_code: "c".to_string(),
+  };
+  let msg = CustomerMessage {
+    name: "a".to_string(),
+    address: Some(AddressMessage {
+      city: "b".to_string(),
+      zip: "c".to_string(),
+    }),
+  };
+  let packed: CustomerMessage = customer.pack_ref().unwrap();
+  assert_eq!(packed, msg);
+  assert_eq!(Customer::unpack_ref(&msg).unwrap(), customer);
+  let packed: Option<CustomerMessage> = customer.pack().unwrap();
+  assert_eq!(packed, Some(msg.clone()));
+  assert_eq!(
+    Customer::unpack(msg).unwrap(),
+    Customer {
+      name: "a".to_string(),
+      city: "b".to_string(),
+      zip_code: "c".to_string(),
+    }
+  );
+
+  let err = Customer::unpack(CustomerMessage {
+    name: "a".to_string(),
+    address: None,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'address' from null"
+  );
+}
+
+#[derive(Debug, PartialEq)]
+enum ColorProto {
+  Unspecified = 0,
+  Red = 1,
+}
+
+impl ColorProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(ColorProto::Unspecified),
+      1 => Some(ColorProto::Red),
+      _ => None,
+    }
+  }
+}
+
+impl From<ColorProto> for i32 {
+  fn from(v: ColorProto) -> i32 {
+    v as i32
+  }
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq)]
+#[s2_grpc(proto_enum_type = "ColorProto", unknown = "Unspecified")]
+enum Color {
+  Unspecified,
+  Red,
+}
+
+#[test]
+fn derive_enum_unknown() {
+  assert_eq!(
+    <Color as S2ProtoUnpack<i32>>::unpack(1).unwrap(),
+    Color::Red
+  );
+  assert_eq!(
+    <Color as S2ProtoUnpack<i32>>::unpack(7).unwrap(),
+    Color::Unspecified
+  );
+  let packed: i32 = Color::Unspecified.pack().unwrap();
+  assert_eq!(packed, 0);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn derive_round_trip() {
+  use s2_grpc_utils::testing::{assert_round_trip, assert_round_trip_ref};
+
+  let packed: NestedMessage = assert_round_trip(NestedModel { v: 1 });
+  assert_eq!(packed, NestedMessage { v: 1 });
+  let packed: Option<NestedMessage> = assert_round_trip_ref(&NestedModel { v: 2 });
+  assert_eq!(packed, Some(NestedMessage { v: 2 }));
+  assert_round_trip::<_, Vec<NestedMessage>>(vec![NestedModel { v: 3 }]);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+#[should_panic(expected = "value changed after a round trip")]
+fn derive_round_trip_lossy() {
+  // numbers in `google.protobuf.Value` are doubles
+  s2_grpc_utils::testing::assert_round_trip::<_, prost_types::Value>(json!(u64::MAX));
+}
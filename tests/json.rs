@@ -0,0 +1,720 @@
+use prost_types::Any;
+use s2_grpc_utils::{
+  Json, JsonTypeUrl, JsonWithUrl, S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef,
+  JSON_TYPE_URL,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+  name: String,
+}
+
+impl JsonTypeUrl for User {
+  fn type_url() -> &'static str {
+    "example/user"
+  }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Group {
+  name: String,
+  size: u64,
+}
+
+impl JsonTypeUrl for Group {
+  fn type_url() -> &'static str {
+    "example/group"
+  }
+}
+
+#[test]
+fn json_any() {
+  let any: Any = Json(vec![1, 2, 3]).pack().unwrap();
+  assert_eq!(any.type_url, JSON_TYPE_URL);
+  assert_eq!(any.value, b"[1,2,3]".to_vec());
+  assert_eq!(Json::<Vec<i32>>::unpack(any).unwrap().0, vec![1, 2, 3]);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Amounts {
+  max: u64,
+  min: i64,
+}
+
+#[test]
+fn json_any_large_integers() {
+  let amounts = Amounts {
+    max: u64::MAX,
+    min: i64::MIN,
+  };
+  let any: Any = s2_grpc_utils::pack_any(&amounts).unwrap();
+  assert_eq!(
+    any.value,
+    br#"{"max":18446744073709551615,"min":-9223372036854775808}"#.to_vec()
+  );
+  assert_eq!(Json::<Amounts>::unpack(any).unwrap().0, amounts);
+}
+
+#[test]
+fn json_with_url() {
+  let user = User {
+    name: "a".to_string(),
+  };
+  let group = Group {
+    name: "b".to_string(),
+    size: 2,
+  };
+
+  let user_any: Any = JsonWithUrl(user).pack().unwrap();
+  assert_eq!(user_any.type_url, "example/user");
+  let group_any: Any = JsonWithUrl(group).pack().unwrap();
+  assert_eq!(group_any.type_url, "example/group");
+
+  assert_eq!(
+    JsonWithUrl::<User>::unpack(user_any.clone()).unwrap().0,
+    User {
+      name: "a".to_string()
+    }
+  );
+  assert_eq!(
+    JsonWithUrl::<Group>::unpack(group_any).unwrap().0,
+    Group {
+      name: "b".to_string(),
+      size: 2
+    }
+  );
+
+  let err = JsonWithUrl::<Group>::unpack(user_any).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not convert json value from type_url: example/user"
+  );
+
+  // packing only needs `Serialize`, unpacking only `Deserialize`
+  #[derive(Serialize)]
+  struct Outgoing {
+    name: &'static str,
+  }
+
+  impl JsonTypeUrl for Outgoing {
+    fn type_url() -> &'static str {
+      "example/user"
+    }
+  }
+
+  #[derive(Debug, PartialEq, Deserialize)]
+  struct Incoming {
+    name: String,
+  }
+
+  impl JsonTypeUrl for Incoming {
+    fn type_url() -> &'static str {
+      "example/user"
+    }
+  }
+
+  let any: Any = JsonWithUrl(Outgoing { name: "c" }).pack().unwrap();
+  assert_eq!(
+    JsonWithUrl::<Incoming>::unpack(any).unwrap().0,
+    Incoming {
+      name: "c".to_string()
+    }
+  );
+}
+
+#[test]
+fn json_any_accepting() {
+  let legacy = Any {
+    type_url: "legacy/json".to_string(),
+    value: b"[1]".to_vec(),
+  };
+  let unpacked =
+    Json::<Vec<i32>>::unpack_any_accepting(legacy, &[JSON_TYPE_URL, "legacy/json"]).unwrap();
+  assert_eq!(unpacked.0, vec![1]);
+
+  let current: Any = Json(vec![2]).pack().unwrap();
+  let unpacked =
+    Json::<Vec<i32>>::unpack_any_accepting(current, &[JSON_TYPE_URL, "legacy/json"]).unwrap();
+  assert_eq!(unpacked.0, vec![2]);
+
+  let unknown = Any {
+    type_url: "unknown/json".to_string(),
+    value: b"[1]".to_vec(),
+  };
+  let err = Json::<Vec<i32>>::unpack_any_accepting(unknown, &[JSON_TYPE_URL, "legacy/json"])
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not convert json value from type_url: unknown/json"
+  );
+}
+
+#[test]
+fn json_struct() {
+  use prost_types::value::Kind;
+  use prost_types::{ListValue, Struct};
+  use serde_json::{json, Value as JsonValue};
+
+  let value = json!({
+    "n": 1.5,
+    "s": "text",
+    "null": null,
+    "list": [true, 2.0]
+  });
+  let packed: Struct = value.clone().pack().unwrap();
+  assert_eq!(packed.fields["n"].kind, Some(Kind::NumberValue(1.5)));
+  assert_eq!(packed.fields["null"].kind, Some(Kind::NullValue(0)));
+  match packed.fields["list"].kind {
+    Some(Kind::ListValue(ref list)) => assert_eq!(list.values.len(), 2),
+    ref other => panic!("unexpected kind: {:?}", other),
+  }
+  assert_eq!(JsonValue::unpack(packed).unwrap(), value);
+
+  let list = json!([1.0, "a"]);
+  let packed: ListValue = list.clone().pack().unwrap();
+  assert_eq!(packed.values.len(), 2);
+  assert_eq!(JsonValue::unpack(packed).unwrap(), list);
+
+  let err = json!([1.0]).pack().map(|_: Struct| ()).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not pack a non-object JSON value into google.protobuf.Struct"
+  );
+}
+
+#[test]
+fn json_any_bytes() {
+  let group = Group {
+    name: "b".to_string(),
+    size: 2,
+  };
+  let expected = serde_json::to_vec(&serde_json::to_value(&group).unwrap()).unwrap();
+  let any: Any = Json(group).pack().unwrap();
+  assert_eq!(any.value, expected);
+}
+
+#[test]
+fn json_pack_ref() {
+  use s2_grpc_utils::S2ProtoPackRef;
+
+  let user = Json(User {
+    name: "a".to_string(),
+  });
+  let any: Any = user.pack_ref().unwrap();
+  assert_eq!(any.value, br#"{"name":"a"}"#.to_vec());
+  let value: prost_types::Value = user.pack_ref().unwrap();
+  assert_eq!(Json::<User>::unpack(value).unwrap().0, user.0);
+}
+
+#[test]
+fn json_pack_borrowed() {
+  let groups: Vec<Group> = (0..1000)
+    .map(|i| Group {
+      name: format!("group {}", i),
+      size: i,
+    })
+    .collect();
+  let any: Any = Json(&groups).pack().unwrap();
+  assert_eq!(any.type_url, JSON_TYPE_URL);
+  assert_eq!(Json::<Vec<Group>>::unpack(any).unwrap().0, groups);
+
+  let value: Option<prost_types::Value> = Json(&groups[0]).pack().unwrap();
+  assert!(value.is_some());
+}
+
+#[test]
+fn json_raw_bytes() {
+  let cached = br#"{"name":"a","size":3}"#.to_vec();
+  let any = Json::from_raw_bytes(cached.clone());
+  assert_eq!(any.type_url, JSON_TYPE_URL);
+  assert_eq!(
+    Json::<Group>::unpack_ref(&any).unwrap().0,
+    Group {
+      name: "a".to_string(),
+      size: 3,
+    }
+  );
+  assert_eq!(Json::into_raw_bytes(any).unwrap(), cached);
+
+  let any: Any = JsonWithUrl(User {
+    name: "a".to_string(),
+  })
+  .pack()
+  .unwrap();
+  let err = Json::into_raw_bytes(any).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not convert json value from type_url: example/user"
+  );
+}
+
+#[derive(Debug, Serialize)]
+struct Sample {
+  name: String,
+  values: Vec<f64>,
+}
+
+#[cfg(not(feature = "strict_floats"))]
+#[test]
+fn json_non_finite_float() {
+  let sample = Sample {
+    name: "a".to_string(),
+    values: vec![1.5, f64::NAN],
+  };
+  let any = s2_grpc_utils::pack_any(&sample).unwrap();
+  assert_eq!(any.value, br#"{"name":"a","values":[1.5,null]}"#.to_vec());
+}
+
+#[cfg(feature = "strict_floats")]
+#[test]
+fn json_non_finite_float() {
+  let sample = Sample {
+    name: "a".to_string(),
+    values: vec![1.5, f64::NAN],
+  };
+  let err = s2_grpc_utils::pack_any(&sample).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not pack non-finite float NaN into JSON"
+  );
+
+  let err = S2ProtoPack::<prost_types::Value>::pack(Json(vec![f32::INFINITY]))
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not pack non-finite float inf into JSON"
+  );
+
+  assert!(s2_grpc_utils::pack_any(&Sample {
+    name: "b".to_string(),
+    values: vec![0.5],
+  })
+  .is_ok());
+}
+
+#[test]
+fn encoded_json() {
+  use s2_grpc_utils::Encoded;
+
+  let group = Group {
+    name: "g".to_string(),
+    size: 2,
+  };
+  let any: Any = Encoded::<_>::new(&group).pack().unwrap();
+  assert_eq!(any.type_url, JSON_TYPE_URL);
+  let json_any = s2_grpc_utils::pack_any(&group).unwrap();
+  assert_eq!(any, json_any);
+
+  let unpacked = Encoded::<Group>::unpack(any).unwrap();
+  assert_eq!(unpacked.into_inner(), group);
+
+  let err = Encoded::<Group>::unpack(Any {
+    type_url: "s2/other".to_string(),
+    value: vec![],
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack Any of type_url s2/other, expected s2/json"
+  );
+}
+
+#[test]
+fn canonical_json() {
+  use s2_grpc_utils::CanonicalJson;
+  use std::collections::HashMap;
+
+  #[derive(Serialize)]
+  struct Before {
+    name: String,
+    size: i32,
+    tags: HashMap<String, i32>,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct After {
+    tags: HashMap<String, i32>,
+    size: i32,
+    name: String,
+  }
+
+  let tags: HashMap<_, _> = (0..16).map(|i| (format!("tag{}", i), i)).collect();
+  let before = Before {
+    name: "g".to_string(),
+    size: 2,
+    tags: tags.clone(),
+  };
+  let after = After {
+    tags,
+    size: 2,
+    name: "g".to_string(),
+  };
+
+  let a: Any = CanonicalJson::new(&before).pack().unwrap();
+  let b: Any = CanonicalJson::new(&after).pack().unwrap();
+  assert_eq!(a.type_url, JSON_TYPE_URL);
+  assert_eq!(a.value, b.value);
+  assert!(a
+    .value
+    .starts_with(br#"{"name":"g","size":2,"tags":{"tag0":0,"tag1":1,"tag10":10,"#));
+
+  let unpacked = CanonicalJson::<After>::unpack(a).unwrap();
+  assert_eq!(unpacked.into_inner(), after);
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn encoded_cbor() {
+  use s2_grpc_utils::{CborFormat, Encoded};
+
+  let group = Group {
+    name: "g".to_string(),
+    size: 2,
+  };
+  let any: Any = Encoded::<_, CborFormat>::new(&group).pack().unwrap();
+  assert_eq!(any.type_url, "s2/cbor");
+  let unpacked = Encoded::<Group, CborFormat>::unpack(any).unwrap();
+  assert_eq!(unpacked.0, group);
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn encoded_msgpack() {
+  use s2_grpc_utils::{Encoded, MsgpackFormat};
+
+  let group = Group {
+    name: "g".to_string(),
+    size: 2,
+  };
+  let any: Option<Any> = Encoded::<_, MsgpackFormat>::new(&group).pack().unwrap();
+  let any = any.unwrap();
+  assert_eq!(any.type_url, "s2/msgpack");
+  let json_any = s2_grpc_utils::pack_any(&group).unwrap();
+  assert!(any.value.len() < json_any.value.len());
+  let unpacked = Encoded::<Group, MsgpackFormat>::unpack(any).unwrap();
+  assert_eq!(unpacked.0, group);
+}
+
+#[test]
+fn json_serde_bytes() {
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Annotated {
+    #[serde(with = "serde_bytes")]
+    digest: Vec<u8>,
+  }
+
+  let annotated = Annotated {
+    digest: (0..64).map(|i| i * 3).collect(),
+  };
+  let any = s2_grpc_utils::pack_any(&annotated).unwrap();
+  assert_eq!(
+    s2_grpc_utils::unpack_any::<Annotated>(any).unwrap(),
+    annotated
+  );
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn json_bytes() {
+  use s2_grpc_utils::Base64;
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Naive {
+    digest: Vec<u8>,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Compact {
+    digest: Base64,
+  }
+
+  let digest: Vec<u8> = (0..64).map(|i| i * 3).collect();
+  let naive = s2_grpc_utils::pack_any(&Naive {
+    digest: digest.clone(),
+  })
+  .unwrap();
+
+  let any: Any = Json(Compact {
+    digest: Base64(digest.clone()),
+  })
+  .pack()
+  .unwrap();
+  assert!(any.value.len() < naive.value.len());
+  assert_eq!(
+    Json::<Compact>::unpack(any).unwrap().0,
+    Compact {
+      digest: Base64(digest),
+    }
+  );
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn encoded_msgpack_bytes() {
+  use s2_grpc_utils::{Encoded, MsgpackFormat};
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Naive {
+    digest: Vec<u8>,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Annotated {
+    #[serde(with = "serde_bytes")]
+    digest: Vec<u8>,
+  }
+
+  let digest: Vec<u8> = (0..64).map(|i| i * 3).collect();
+  let naive: Any = Encoded::<_, MsgpackFormat>::new(Naive {
+    digest: digest.clone(),
+  })
+  .pack()
+  .unwrap();
+  let annotated = Annotated { digest };
+  let any: Any = Encoded::<_, MsgpackFormat>::new(&annotated).pack().unwrap();
+  assert!(any.value.len() < naive.value.len());
+  assert_eq!(
+    Encoded::<Annotated, MsgpackFormat>::unpack(any).unwrap().0,
+    annotated
+  );
+}
+
+#[test]
+fn json_nullable() {
+  use prost_types::value::Kind;
+  use prost_types::Value;
+
+  type Nullable = Option<Option<Json<User>>>;
+
+  // absent
+  let packed: Option<Value> = None::<Option<Json<User>>>.pack().unwrap();
+  assert_eq!(packed, None);
+  assert!(Nullable::unpack(packed).unwrap().is_none());
+
+  // present, null
+  let packed: Option<Value> = Some(None::<Json<User>>).pack().unwrap();
+  assert!(matches!(
+    packed.as_ref().unwrap().kind,
+    Some(Kind::NullValue(_))
+  ));
+  assert!(matches!(Nullable::unpack(packed).unwrap(), Some(None)));
+  assert!(matches!(
+    Nullable::unpack(Some(Value { kind: None })).unwrap(),
+    Some(None)
+  ));
+
+  // present, value
+  let user = Some(Some(Json(User {
+    name: "a".to_string(),
+  })));
+  let packed: Option<Value> = user.pack_ref().unwrap();
+  assert!(matches!(
+    packed.as_ref().unwrap().kind,
+    Some(Kind::StructValue(_))
+  ));
+  let unpacked = Nullable::unpack_ref(&packed).unwrap();
+  assert_eq!(
+    unpacked.unwrap().unwrap().0,
+    User {
+      name: "a".to_string()
+    }
+  );
+}
+
+#[test]
+fn unpack_any_limited() {
+  use s2_grpc_utils::result::Error;
+  use s2_grpc_utils::unpack_any_limited;
+
+  let nested = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+  let any = Any {
+    type_url: JSON_TYPE_URL.to_string(),
+    value: nested.into_bytes(),
+  };
+  let err = unpack_any_limited::<serde_json::Value>(any, 32)
+    .err()
+    .unwrap();
+  assert!(matches!(err, Error::DepthLimitExceeded { limit: 32 }));
+  assert_eq!(
+    format!("{}", err),
+    "JSON payload is nested deeper than the limit of 32"
+  );
+
+  // brackets inside strings don't count
+  let user = User {
+    name: r#"[[[{{"\"#.to_string(),
+  };
+  let any = s2_grpc_utils::pack_any(&user).unwrap();
+  assert_eq!(unpack_any_limited::<User>(any.clone(), 1).unwrap(), user);
+
+  let err = unpack_any_limited::<User>(any, 0).err().unwrap();
+  assert!(matches!(err, Error::DepthLimitExceeded { limit: 0 }));
+}
+
+#[test]
+fn json_map_any() {
+  use std::collections::HashMap;
+
+  let object = serde_json::json!({
+    "name": "a",
+    "size": 3,
+    "tags": ["x", "y"],
+    "owner": { "id": 1 },
+    "deleted": null
+  });
+  let map = object.as_object().unwrap().clone();
+
+  let packed: HashMap<String, Any> = map.pack_ref().unwrap();
+  assert_eq!(packed.len(), 5);
+  assert_eq!(packed["size"].type_url, JSON_TYPE_URL);
+  assert_eq!(packed["tags"].value, br#"["x","y"]"#.to_vec());
+  assert_eq!(
+    serde_json::Map::<String, serde_json::Value>::unpack_ref(&packed).unwrap(),
+    map
+  );
+  assert_eq!(
+    serde_json::Map::<String, serde_json::Value>::unpack(packed).unwrap(),
+    map
+  );
+
+  let mut packed = HashMap::new();
+  packed.insert(
+    "a".to_string(),
+    Any {
+      type_url: "example/user".to_string(),
+      value: vec![],
+    },
+  );
+  let err = serde_json::Map::<String, serde_json::Value>::unpack(packed)
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Map entry: Could not convert json value from type_url: example/user"
+  );
+}
+
+#[test]
+fn json_error_source() {
+  use std::error::Error as _;
+
+  // what `anyhow::Error` and `Box<dyn Error + Send + Sync>` require
+  fn assert_error<E: std::error::Error + Send + Sync + 'static>() {}
+  assert_error::<s2_grpc_utils::result::Error>();
+
+  let err = Json::<User>::unpack(Any {
+    type_url: JSON_TYPE_URL.to_string(),
+    value: b"{".to_vec(),
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "JSON error: EOF while parsing an object at line 1 column 1"
+  );
+  let source = err.source().unwrap();
+  assert!(source.is::<serde_json::Error>());
+  assert_eq!(
+    source.to_string(),
+    "EOF while parsing an object at line 1 column 1"
+  );
+
+  let err = Json::<User>::unpack(Any {
+    type_url: "example/user".to_string(),
+    value: vec![],
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not convert json value from type_url: example/user"
+  );
+  assert!(err.source().is_none());
+
+  // Errors nested at a field path keep the whole chain
+  let err = err.at("owner");
+  assert_eq!(
+    format!("{}", err),
+    "at owner: Could not convert json value from type_url: example/user"
+  );
+  assert_eq!(
+    err.source().unwrap().to_string(),
+    "Could not convert json value from type_url: example/user"
+  );
+}
+
+#[test]
+fn json_any_into_buffer() {
+  let groups: Vec<_> = (0..100)
+    .map(|i| Group {
+      name: format!("group {}", i),
+      size: i,
+    })
+    .collect();
+
+  let mut buf = Vec::new();
+  let mut capacity = 0;
+  for group in &groups {
+    let any = s2_grpc_utils::pack_any_into(group, &mut buf).unwrap();
+    assert_eq!(any, s2_grpc_utils::pack_any(group).unwrap());
+    assert_eq!(any.value.len(), any.value.capacity());
+    assert!(buf.capacity() >= capacity);
+    capacity = buf.capacity();
+    assert_eq!(Json::<Group>::unpack(any).unwrap().0, *group);
+  }
+}
+
+#[test]
+fn erased_pack_unpack() {
+  use s2_grpc_utils::{ErasedPack, ErasedUnpack, Unpacker};
+  use std::collections::HashMap;
+
+  let values: Vec<Box<dyn ErasedPack>> = vec![
+    Box::new(User {
+      name: "a".to_string(),
+    }),
+    Box::new(Group {
+      name: "b".to_string(),
+      size: 2,
+    }),
+  ];
+  let mut unpackers: HashMap<&'static str, Box<dyn ErasedUnpack>> = HashMap::new();
+  for unpacker in [
+    Box::new(Unpacker::<User>::new()) as Box<dyn ErasedUnpack>,
+    Box::new(Unpacker::<Group>::new()),
+  ] {
+    unpackers.insert(unpacker.type_name(), unpacker);
+  }
+
+  let value = values[0].pack_value().unwrap();
+  let unpacked = unpackers[values[0].type_name()]
+    .unpack_value(value)
+    .unwrap();
+  assert_eq!(
+    unpacked.downcast_ref::<User>(),
+    Some(&User {
+      name: "a".to_string()
+    })
+  );
+
+  // `google.protobuf.Value` numbers are doubles, integers round-trip through `Any`
+  let any = values[1].pack_any().unwrap();
+  assert_eq!(any.type_url, JSON_TYPE_URL);
+  let unpacked = unpackers[values[1].type_name()].unpack_any(&any).unwrap();
+  assert_eq!(
+    unpacked.downcast::<Group>().ok().map(|g| *g),
+    Some(Group {
+      name: "b".to_string(),
+      size: 2,
+    })
+  );
+
+  let value = values[0].pack_value().unwrap();
+  assert!(unpackers[values[1].type_name()]
+    .unpack_value(value)
+    .is_err());
+}
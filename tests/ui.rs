@@ -0,0 +1,6 @@
+#[test]
+fn ui() {
+  let t = trybuild::TestCases::new();
+  t.compile_fail("tests/ui/*.rs");
+  t.pass("tests/ui/pass/*.rs");
+}
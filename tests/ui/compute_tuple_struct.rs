@@ -0,0 +1,11 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+struct PairMessage {
+  a: i32,
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "PairMessage")]
+struct Pair(#[s2_grpc(rename = "a")] i32, #[s2_grpc(compute = "1")] i32);
+
+fn main() {}
@@ -0,0 +1,15 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+struct UserMessage {
+  user_id: i64,
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "UserMessage")]
+struct User {
+  user_id: i64,
+  #[s2_grpc(default = "1")]
+  cached: i64,
+}
+
+fn main() {}
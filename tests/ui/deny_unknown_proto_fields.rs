@@ -0,0 +1,16 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+struct UserMessage {
+  name: String,
+  email: String,
+  nickname: String,
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "UserMessage", deny_unknown_proto_fields)]
+struct User {
+  name: String,
+  email: String,
+}
+
+fn main() {}
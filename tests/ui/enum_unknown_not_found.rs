@@ -0,0 +1,22 @@
+use s2_grpc_utils::S2ProtoEnum;
+
+enum ColorProto {
+  Red = 0,
+}
+
+impl ColorProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(ColorProto::Red),
+      _ => None,
+    }
+  }
+}
+
+#[derive(S2ProtoEnum)]
+#[s2_grpc(proto_enum_type = "ColorProto", unknown = "Other")]
+enum Color {
+  Red,
+}
+
+fn main() {}
@@ -0,0 +1,36 @@
+use s2_grpc_utils::{result::Result, S2ProtoPack, S2ProtoUnpack};
+
+/// Converts from/to a packed `int64` or `bytes`, but not from/to `string`
+struct Color(u8, u8, u8);
+
+impl S2ProtoPack<i64> for Color {
+  fn pack(self) -> Result<i64> {
+    Ok(((self.0 as i64) << 16) | ((self.1 as i64) << 8) | self.2 as i64)
+  }
+}
+
+impl S2ProtoPack<Vec<u8>> for Color {
+  fn pack(self) -> Result<Vec<u8>> {
+    Ok(vec![self.0, self.1, self.2])
+  }
+}
+
+impl S2ProtoUnpack<i64> for Color {
+  fn unpack(v: i64) -> Result<Color> {
+    Ok(Color((v >> 16) as u8, (v >> 8) as u8, v as u8))
+  }
+}
+
+struct EventMessage {
+  name: String,
+  color: String,
+}
+
+#[derive(S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "EventMessage")]
+struct Event {
+  name: String,
+  color: Color,
+}
+
+fn main() {}
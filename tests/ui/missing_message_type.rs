@@ -0,0 +1,8 @@
+use s2_grpc_utils::S2ProtoPack;
+
+#[derive(S2ProtoPack)]
+struct Point {
+  x: i32,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+struct TicketMessage {
+  priority: i32,
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "TicketMessage")]
+struct Ticket {
+  #[s2_grpc(none_on_unspecified)]
+  priority: i32,
+}
+
+fn main() {}
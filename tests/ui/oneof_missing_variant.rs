@@ -0,0 +1,16 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+mod shape_message {
+  pub enum Kind {
+    Radius(f64),
+    Label(String),
+  }
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "shape_message::Kind")]
+enum ShapeKind {
+  Radius(f64),
+}
+
+fn main() {}
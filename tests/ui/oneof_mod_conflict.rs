@@ -0,0 +1,15 @@
+use s2_grpc_utils::S2ProtoPack;
+
+mod shape_message {
+  pub enum Kind {
+    Radius(f64),
+  }
+}
+
+#[derive(S2ProtoPack)]
+#[s2_grpc(message_type = "shape_message::Kind", oneof_mod = "shape_message::Kind")]
+enum ShapeKind {
+  Radius(f64),
+}
+
+fn main() {}
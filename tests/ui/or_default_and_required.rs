@@ -0,0 +1,14 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+struct SettingsMessage {
+  limits: Option<i32>,
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "SettingsMessage")]
+struct Settings {
+  #[s2_grpc(or_default, required)]
+  limits: i32,
+}
+
+fn main() {}
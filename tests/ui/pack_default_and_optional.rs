@@ -0,0 +1,14 @@
+use s2_grpc_utils::S2ProtoPack;
+
+struct ReportMessage {
+  lines: u32,
+}
+
+#[derive(S2ProtoPack)]
+#[s2_grpc(message_type = "ReportMessage")]
+struct Report {
+  #[s2_grpc(pack_default, optional)]
+  lines: Option<u32>,
+}
+
+fn main() {}
@@ -0,0 +1,35 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[derive(Debug, PartialEq)]
+struct PersonMessage {
+  first: String,
+  last: String,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "PersonMessage", unpack_ref, collect_errors)]
+struct Person {
+  first: String,
+  last: String,
+  #[s2_grpc(compute = "format!(\"{} {}\", first, last)")]
+  full_name: String,
+}
+
+fn main() {
+  let msg = PersonMessage {
+    first: "Ada".to_string(),
+    last: "Lovelace".to_string(),
+  };
+  let person = Person::unpack_ref(&msg).unwrap();
+  assert_eq!(person.full_name, "Ada Lovelace");
+  assert_eq!(Person::unpack(msg).unwrap(), person);
+
+  let packed: PersonMessage = person.pack().unwrap();
+  assert_eq!(
+    packed,
+    PersonMessage {
+      first: "Ada".to_string(),
+      last: "Lovelace".to_string(),
+    }
+  );
+}
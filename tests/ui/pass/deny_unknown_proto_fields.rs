@@ -0,0 +1,52 @@
+use s2_grpc_utils::{S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[derive(Clone)]
+struct ProfileMessage {
+  bio: String,
+}
+
+#[derive(Clone)]
+struct UserMessage {
+  name: String,
+  email_address: String,
+  age: u32,
+  profile: Option<ProfileMessage>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoUnpack)]
+#[s2_grpc(
+  message_type = "UserMessage",
+  unpack_ref,
+  deny_unknown_proto_fields
+)]
+struct User {
+  name: String,
+  #[s2_grpc(proto_name = "email_address")]
+  email: String,
+  #[s2_grpc(map_fn = "u64::from")]
+  age: u64,
+  #[s2_grpc(flatten = "profile")]
+  bio: String,
+  #[s2_grpc(skip)]
+  verified: bool,
+}
+
+fn main() {
+  let msg = UserMessage {
+    name: "a".to_string(),
+    email_address: "a@example.com".to_string(),
+    age: 30,
+    profile: Some(ProfileMessage {
+      bio: "hi".to_string(),
+    }),
+  };
+  let user = User {
+    name: "a".to_string(),
+    email: "a@example.com".to_string(),
+    age: 30,
+    bio: "hi".to_string(),
+    verified: false,
+  };
+  assert_eq!(User::unpack_ref(&msg).unwrap(), user);
+  assert_eq!(User::unpack(msg).unwrap(), user);
+}
@@ -0,0 +1,61 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+// Plain structs from another module, not generated by prost
+mod storage {
+  #[derive(Debug, PartialEq)]
+  pub struct AccountRow {
+    pub id: i64,
+    pub display_name: String,
+    pub tags: Vec<String>,
+    pub owner: Option<UserRow>,
+  }
+
+  #[derive(Debug, PartialEq)]
+  pub struct UserRow {
+    pub id: i64,
+  }
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "storage::UserRow")]
+struct User {
+  id: i64,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "storage::AccountRow")]
+struct Account {
+  id: i64,
+  #[s2_grpc(proto_name = "display_name")]
+  name: String,
+  tags: Vec<String>,
+  owner: User,
+}
+
+fn main() {
+  let account = Account {
+    id: 1,
+    name: "a".to_string(),
+    tags: vec!["x".to_string()],
+    owner: User { id: 2 },
+  };
+  let row: storage::AccountRow = account.pack().unwrap();
+  assert_eq!(
+    row,
+    storage::AccountRow {
+      id: 1,
+      display_name: "a".to_string(),
+      tags: vec!["x".to_string()],
+      owner: Some(storage::UserRow { id: 2 }),
+    }
+  );
+  assert_eq!(
+    Account::unpack(row).unwrap(),
+    Account {
+      id: 1,
+      name: "a".to_string(),
+      tags: vec!["x".to_string()],
+      owner: User { id: 2 },
+    }
+  );
+}
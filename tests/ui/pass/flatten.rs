@@ -0,0 +1,47 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+#[derive(Debug, PartialEq, Default)]
+struct LocationMessage {
+  lat: f64,
+  lng: f64,
+}
+
+#[derive(Debug, PartialEq)]
+struct PlaceMessage {
+  name: String,
+  location: Option<LocationMessage>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "PlaceMessage")]
+struct Place {
+  name: String,
+  #[s2_grpc(flatten = "location")]
+  lat: f64,
+  #[s2_grpc(flatten = "location", rename = "lng")]
+  longitude: f64,
+}
+
+fn main() {
+  let place = Place {
+    name: "a".to_string(),
+    lat: 1.0,
+    longitude: 2.0,
+  };
+  let msg: PlaceMessage = place.pack().unwrap();
+  assert_eq!(
+    msg,
+    PlaceMessage {
+      name: "a".to_string(),
+      location: Some(LocationMessage { lat: 1.0, lng: 2.0 }),
+    }
+  );
+  assert_eq!(
+    Place::unpack(msg).unwrap(),
+    Place {
+      name: "a".to_string(),
+      lat: 1.0,
+      longitude: 2.0,
+    }
+  );
+}
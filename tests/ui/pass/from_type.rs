@@ -0,0 +1,79 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct MyId(u64);
+
+impl From<u64> for MyId {
+  fn from(value: u64) -> MyId {
+    MyId(value)
+  }
+}
+
+impl From<MyId> for u64 {
+  fn from(value: MyId) -> u64 {
+    value.0
+  }
+}
+
+// Converts the intermediate `u64`, not `MyId`
+mod decimal_string {
+  use s2_grpc_utils::result::{Error, Result};
+
+  pub fn pack(value: u64) -> Result<String> {
+    Ok(value.to_string())
+  }
+
+  pub fn unpack(value: String) -> Result<u64> {
+    value.parse().map_err(|source| Error::ParseInt { source })
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct OrderMessage {
+  id: u64,
+  customer_id: Option<u64>,
+  reference: String,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "OrderMessage", pack_ref, unpack_ref)]
+struct Order {
+  #[s2_grpc(from = "u64")]
+  id: MyId,
+  #[s2_grpc(from = "u64")]
+  customer_id: MyId,
+  #[s2_grpc(from = "u64", with = "decimal_string")]
+  reference: MyId,
+}
+
+fn main() {
+  let order = Order {
+    id: MyId(1),
+    customer_id: MyId(2),
+    reference: MyId(3),
+  };
+  let msg: OrderMessage = order.pack_ref().unwrap();
+  assert_eq!(
+    msg,
+    OrderMessage {
+      id: 1,
+      customer_id: Some(2),
+      reference: "3".to_string(),
+    }
+  );
+  assert_eq!(Order::unpack_ref(&msg).unwrap(), order);
+  assert_eq!(Order::unpack(msg.clone()).unwrap(), order);
+  let packed: OrderMessage = order.pack().unwrap();
+  assert_eq!(packed, msg);
+
+  let err = Order::unpack(OrderMessage {
+    customer_id: None,
+    ..msg
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'customer_id' from null"
+  );
+}
@@ -0,0 +1,34 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+#[derive(Debug, PartialEq)]
+struct EnvelopeMessage {
+  id: i64,
+  payload: Option<String>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(
+  message_type = "EnvelopeMessage",
+  bound = "T: S2ProtoPack<Option<String>> + S2ProtoUnpack<Option<String>>"
+)]
+struct Envelope<T> {
+  id: i64,
+  payload: T,
+}
+
+fn main() {
+  let envelope = Envelope {
+    id: 1,
+    payload: 12345_u128,
+  };
+  let msg: EnvelopeMessage = envelope.pack().unwrap();
+  assert_eq!(
+    msg,
+    EnvelopeMessage {
+      id: 1,
+      payload: Some("12345".to_string()),
+    }
+  );
+  let unpacked = Envelope::<u128>::unpack(msg).unwrap();
+  assert_eq!(unpacked.payload, 12345);
+}
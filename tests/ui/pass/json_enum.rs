@@ -0,0 +1,51 @@
+use prost_types::{Any, Value};
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(json, pack_ref, unpack_ref)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Trigger {
+  Manual,
+  Cron { schedule: String },
+  Webhook { url: String, secret: Option<String> },
+}
+
+#[derive(Debug, PartialEq)]
+struct JobMessage {
+  trigger: Option<Any>,
+  fallback: Option<Value>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "JobMessage")]
+struct Job {
+  trigger: Trigger,
+  fallback: Trigger,
+}
+
+fn main() {
+  let job = Job {
+    trigger: Trigger::Cron {
+      schedule: "0 * * * *".to_string(),
+    },
+    fallback: Trigger::Manual,
+  };
+  let msg: JobMessage = job.pack().unwrap();
+  assert_eq!(msg.trigger.as_ref().unwrap().type_url, s2_grpc_utils::JSON_TYPE_URL);
+  assert_eq!(
+    msg.trigger.as_ref().unwrap().value,
+    br#"{"type":"cron","schedule":"0 * * * *"}"#.to_vec()
+  );
+  let job = Job::unpack(msg).unwrap();
+  assert_eq!(job.fallback, Trigger::Manual);
+
+  let webhook = Trigger::Webhook {
+    url: "https://example.com".to_string(),
+    secret: None,
+  };
+  let value: Value = webhook.pack_ref().unwrap();
+  assert_eq!(Trigger::unpack_ref(&value).unwrap(), webhook);
+  let any: Any = webhook.clone().pack().unwrap();
+  assert_eq!(Trigger::unpack(any).unwrap(), webhook);
+}
@@ -0,0 +1,50 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+mod proto {
+  pub mod v1 {
+    #[derive(Debug, PartialEq)]
+    pub struct User {
+      pub id: i64,
+      pub name: String,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct GroupMessage {
+      pub owner: Option<User>,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_mod = "proto::v1")]
+struct User {
+  id: i64,
+  name: String,
+}
+
+// `message_type` overrides the inferred `proto::v1::Group`
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_mod = "proto::v1", message_type = "proto::v1::GroupMessage")]
+struct Group {
+  owner: User,
+}
+
+fn main() {
+  let group = Group {
+    owner: User {
+      id: 1,
+      name: "a".to_string(),
+    },
+  };
+  let msg: proto::v1::GroupMessage = group.pack().unwrap();
+  assert_eq!(
+    msg,
+    proto::v1::GroupMessage {
+      owner: Some(proto::v1::User {
+        id: 1,
+        name: "a".to_string(),
+      }),
+    }
+  );
+  assert_eq!(Group::unpack(msg).unwrap().owner.id, 1);
+}
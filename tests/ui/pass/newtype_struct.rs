@@ -0,0 +1,52 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack};
+
+#[derive(Debug, PartialEq, Clone, Copy, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(pack_ref)]
+struct UserId(u64);
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+struct Code(i8);
+
+#[derive(Debug, PartialEq)]
+struct UserMessage {
+  id: u64,
+  parent_id: Option<u64>,
+  code: i32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "UserMessage")]
+struct User {
+  id: UserId,
+  parent_id: Option<UserId>,
+  code: Code,
+}
+
+fn main() {
+  let packed: u64 = UserId(1).pack().unwrap();
+  assert_eq!(packed, 1);
+  let packed: Option<u64> = UserId(1).pack_ref().unwrap();
+  assert_eq!(packed, Some(1));
+  assert_eq!(UserId::unpack(2_u64).unwrap(), UserId(2));
+
+  let user = User {
+    id: UserId(1),
+    parent_id: Some(UserId(2)),
+    code: Code(-3),
+  };
+  let msg: UserMessage = user.pack().unwrap();
+  assert_eq!(
+    msg,
+    UserMessage {
+      id: 1,
+      parent_id: Some(2),
+      code: -3,
+    }
+  );
+  let unpacked = User::unpack(msg).unwrap();
+  assert_eq!(unpacked.id, UserId(1));
+  assert_eq!(unpacked.parent_id, Some(UserId(2)));
+
+  let err = Code::unpack(300).err().unwrap();
+  assert_eq!(format!("{}", err), "Integer value 300 does not fit in i8");
+}
@@ -0,0 +1,52 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+// Layout generated by prost for `message Event { oneof payload { ... } }` in package `events.v1`
+pub mod events {
+  pub mod v1 {
+    #[derive(Debug, PartialEq)]
+    pub struct Event {
+      pub payload: Option<event::Payload>,
+    }
+
+    pub mod event {
+      #[derive(Debug, PartialEq)]
+      pub enum Payload {
+        Created(String),
+        Deleted(i64),
+      }
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(oneof_mod = "events::v1::event::Payload")]
+enum Payload {
+  Created(String),
+  #[s2_grpc(rename = "Deleted")]
+  Removed(i64),
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "events::v1::Event")]
+struct Event {
+  payload: Payload,
+}
+
+fn main() {
+  let event = Event {
+    payload: Payload::Removed(1),
+  };
+  let msg: events::v1::Event = event.pack().unwrap();
+  assert_eq!(
+    msg,
+    events::v1::Event {
+      payload: Some(events::v1::event::Payload::Deleted(1)),
+    }
+  );
+  assert_eq!(
+    Event::unpack(msg).unwrap(),
+    Event {
+      payload: Payload::Removed(1),
+    }
+  );
+}
@@ -0,0 +1,64 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct RetryPolicyMessage {
+  max_attempts: u32,
+  backoff_ms: u64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct JobMessage {
+  name: String,
+  retry: Option<RetryPolicyMessage>,
+}
+
+#[derive(Debug, PartialEq, Default, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "RetryPolicyMessage", pack_ref, unpack_ref)]
+struct RetryPolicy {
+  max_attempts: u32,
+  backoff_ms: u64,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "JobMessage", pack_ref, unpack_ref)]
+struct Job {
+  name: String,
+  #[s2_grpc(or_default)]
+  retry: RetryPolicy,
+}
+
+fn main() {
+  let msg = JobMessage {
+    name: "a".to_string(),
+    retry: None,
+  };
+  let job = Job::unpack_ref(&msg).unwrap();
+  assert_eq!(job.retry, RetryPolicy::default());
+  let job = Job::unpack(msg).unwrap();
+  assert_eq!(
+    job,
+    Job {
+      name: "a".to_string(),
+      retry: RetryPolicy::default(),
+    }
+  );
+
+  let job = Job {
+    name: "b".to_string(),
+    retry: RetryPolicy {
+      max_attempts: 3,
+      backoff_ms: 100,
+    },
+  };
+  let msg: JobMessage = job.pack_ref().unwrap();
+  assert_eq!(
+    msg.retry,
+    Some(RetryPolicyMessage {
+      max_attempts: 3,
+      backoff_ms: 100,
+    })
+  );
+  assert_eq!(Job::unpack(msg.clone()).unwrap(), job);
+  let packed: JobMessage = Job::unpack(msg).unwrap().pack().unwrap();
+  assert!(packed.retry.is_some());
+}
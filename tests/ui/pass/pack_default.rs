@@ -0,0 +1,58 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack};
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct SummaryMessage {
+  lines: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct ReportMessage {
+  title: String,
+  summary: Option<SummaryMessage>,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "SummaryMessage", pack_ref)]
+struct Summary {
+  lines: u32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "ReportMessage", pack_ref)]
+struct Report {
+  title: String,
+  #[s2_grpc(pack_default)]
+  summary: Option<Summary>,
+}
+
+fn main() {
+  let report = Report {
+    title: "a".to_string(),
+    summary: None,
+  };
+  let msg: ReportMessage = report.pack_ref().unwrap();
+  assert_eq!(msg.summary, Some(SummaryMessage::default()));
+  let msg: ReportMessage = report.pack().unwrap();
+  assert_eq!(msg.summary, Some(SummaryMessage::default()));
+  assert_eq!(
+    Report::unpack(msg).unwrap(),
+    Report {
+      title: "a".to_string(),
+      summary: Some(Summary { lines: 0 }),
+    }
+  );
+
+  let report = Report {
+    title: "b".to_string(),
+    summary: Some(Summary { lines: 3 }),
+  };
+  let msg: ReportMessage = report.pack_ref().unwrap();
+  assert_eq!(msg.summary, Some(SummaryMessage { lines: 3 }));
+  assert_eq!(Report::unpack(msg).unwrap(), report);
+
+  let msg = ReportMessage {
+    title: "c".to_string(),
+    summary: None,
+  };
+  assert_eq!(Report::unpack(msg).unwrap().summary, None);
+}
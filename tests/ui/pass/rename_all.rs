@@ -0,0 +1,61 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[allow(non_snake_case)]
+#[derive(Debug, PartialEq, Clone)]
+struct AccountMessage {
+  accountId: i64,
+  displayName: String,
+  isActive: bool,
+  legacy_code: String,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(
+  message_type = "AccountMessage",
+  rename_all = "camelCase",
+  pack_ref,
+  unpack_ref
+)]
+struct Account {
+  account_id: i64,
+  display_name: String,
+  is_active: bool,
+  #[s2_grpc(proto_name = "legacy_code")]
+  code: String,
+}
+
+#[allow(non_snake_case)]
+struct LimitsMessage {
+  MAX_USERS: u32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "LimitsMessage", rename_all = "SCREAMING_SNAKE_CASE")]
+struct Limits {
+  max_users: u32,
+}
+
+fn main() {
+  let account = Account {
+    account_id: 1,
+    display_name: "a".to_string(),
+    is_active: true,
+    code: "x".to_string(),
+  };
+  let msg: AccountMessage = account.pack_ref().unwrap();
+  assert_eq!(
+    msg,
+    AccountMessage {
+      accountId: 1,
+      displayName: "a".to_string(),
+      isActive: true,
+      legacy_code: "x".to_string(),
+    }
+  );
+  assert_eq!(Account::unpack_ref(&msg).unwrap(), account);
+  assert_eq!(Account::unpack(msg).unwrap(), account);
+
+  let msg: LimitsMessage = Limits { max_users: 5 }.pack().unwrap();
+  assert_eq!(msg.MAX_USERS, 5);
+  assert_eq!(Limits::unpack(msg).unwrap(), Limits { max_users: 5 });
+}
@@ -0,0 +1,87 @@
+use s2_grpc_utils::result::Result;
+use s2_grpc_utils::{S2ProtoPack, S2ProtoPackRef, S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct PointMessage {
+  x: i32,
+  y: i32,
+}
+
+// Only the plain conversions, without the `Option` ones
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Point(i32, i32);
+
+impl S2ProtoPack<PointMessage> for Point {
+  fn pack(self) -> Result<PointMessage> {
+    Ok(PointMessage {
+      x: self.0,
+      y: self.1,
+    })
+  }
+}
+
+impl S2ProtoPackRef<PointMessage> for Point {
+  fn pack_ref(&self) -> Result<PointMessage> {
+    self.pack()
+  }
+}
+
+impl S2ProtoUnpack<PointMessage> for Point {
+  fn unpack(value: PointMessage) -> Result<Point> {
+    Ok(Point(value.x, value.y))
+  }
+}
+
+impl S2ProtoUnpackRef<PointMessage> for Point {
+  fn unpack_ref(value: &PointMessage) -> Result<Point> {
+    Point::unpack(value.clone())
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct LineMessage {
+  start: Option<PointMessage>,
+  end: PointMessage,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "LineMessage", pack_ref, unpack_ref)]
+struct Line {
+  #[s2_grpc(required)]
+  start: Point,
+  #[s2_grpc(optional)]
+  end: Option<Point>,
+}
+
+fn main() {
+  let line = Line {
+    start: Point(1, 2),
+    end: Some(Point(3, 4)),
+  };
+  let msg: LineMessage = line.pack_ref().unwrap();
+  assert_eq!(
+    msg,
+    LineMessage {
+      start: Some(PointMessage { x: 1, y: 2 }),
+      end: PointMessage { x: 3, y: 4 },
+    }
+  );
+  assert_eq!(Line::unpack_ref(&msg).unwrap(), line);
+  assert_eq!(Line::unpack(msg).unwrap(), line);
+
+  let msg: LineMessage = Line {
+    start: Point(1, 2),
+    end: None,
+  }
+  .pack()
+  .unwrap();
+  assert_eq!(msg.end, PointMessage::default());
+
+  let err = Line::unpack(LineMessage {
+    start: None,
+    end: PointMessage::default(),
+  })
+  .err()
+  .unwrap();
+  assert_eq!(format!("{}", err), "Could not unpack field 'start' from null");
+}
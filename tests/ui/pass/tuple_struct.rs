@@ -0,0 +1,25 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+#[derive(Debug, PartialEq)]
+struct PointMessage {
+  x: i32,
+  y: i32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "PointMessage")]
+struct Point(#[s2_grpc(rename = "x")] i16, #[s2_grpc(rename = "y")] i16);
+
+fn main() {
+  let msg: PointMessage = Point(1, -2).pack().unwrap();
+  assert_eq!(msg, PointMessage { x: 1, y: -2 });
+  assert_eq!(Point::unpack(msg).unwrap(), Point(1, -2));
+
+  let err = Point::unpack(PointMessage { x: 1, y: 40000 })
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at 1: Integer value 40000 does not fit in i16"
+  );
+}
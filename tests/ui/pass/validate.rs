@@ -0,0 +1,51 @@
+use s2_grpc_utils::{S2ProtoUnpack, S2ProtoUnpackRef};
+
+#[derive(Debug, PartialEq)]
+struct UserMessage {
+  name: String,
+  age: i32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoUnpack)]
+#[s2_grpc(message_type = "UserMessage", unpack_ref, validate = "validate_user")]
+struct User {
+  name: String,
+  age: i32,
+}
+
+fn validate_user(user: &User) -> Result<(), String> {
+  if user.name.is_empty() {
+    return Err("name must not be empty".to_string());
+  }
+  Ok(())
+}
+
+fn main() {
+  let user = User::unpack(UserMessage {
+    name: "a".to_string(),
+    age: 1,
+  })
+  .unwrap();
+  assert_eq!(
+    user,
+    User {
+      name: "a".to_string(),
+      age: 1,
+    }
+  );
+
+  let msg = UserMessage {
+    name: "".to_string(),
+    age: 1,
+  };
+  let err = User::unpack_ref(&msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Validation failed: name must not be empty"
+  );
+  let err = User::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Validation failed: name must not be empty"
+  );
+}
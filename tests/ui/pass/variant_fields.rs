@@ -0,0 +1,54 @@
+use s2_grpc_utils::result::Error;
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+// A message that predates `oneof`: exactly one of the fields is expected to be set
+#[derive(Debug, Default, PartialEq)]
+pub struct Contact {
+  pub email_address: String,
+  pub phone: String,
+  pub user_id: i64,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "Contact", variant_fields, pack_ref, unpack_ref)]
+enum ContactKind {
+  EmailAddress(String),
+  #[s2_grpc(rename = "phone")]
+  PhoneNumber(String),
+  UserId(i64),
+}
+
+fn main() {
+  let packed: Contact = ContactKind::UserId(7).pack().unwrap();
+  assert_eq!(
+    packed,
+    Contact {
+      user_id: 7,
+      ..Default::default()
+    }
+  );
+  assert_eq!(ContactKind::unpack(packed).unwrap(), ContactKind::UserId(7));
+
+  let kind = ContactKind::PhoneNumber("555".to_string());
+  let packed: Contact = s2_grpc_utils::S2ProtoPackRef::pack_ref(&kind).unwrap();
+  assert_eq!(packed.phone, "555");
+  let unpacked: ContactKind = s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(&packed).unwrap();
+  assert_eq!(unpacked, kind);
+
+  assert!(matches!(
+    ContactKind::unpack(Contact::default()),
+    Err(Error::ValueNotPresent)
+  ));
+
+  let err = ContactKind::unpack(Contact {
+    email_address: "a@b.c".to_string(),
+    user_id: 7,
+    ..Default::default()
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    err.to_string(),
+    "Only one of the variant fields can be set, got: email_address, user_id"
+  );
+}
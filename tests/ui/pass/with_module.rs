@@ -0,0 +1,67 @@
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Permissions(u8);
+
+mod permissions_bits {
+  use super::Permissions;
+  use s2_grpc_utils::result::{Error, Result};
+
+  pub fn pack(value: Permissions) -> Result<i32> {
+    Ok(i32::from(value.0))
+  }
+
+  pub fn unpack(value: i32) -> Result<Permissions> {
+    if value & !0b111 != 0 {
+      return Err(Error::IntegerOverflow {
+        value: i64::from(value),
+        target: "Permissions",
+      });
+    }
+    Ok(Permissions(value as u8))
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct FileMessage {
+  name: String,
+  mode: i32,
+}
+
+#[derive(Debug, PartialEq, S2ProtoPack, S2ProtoUnpack)]
+#[s2_grpc(message_type = "FileMessage", pack_ref, unpack_ref)]
+struct File {
+  name: String,
+  #[s2_grpc(with = "permissions_bits")]
+  mode: Permissions,
+}
+
+fn main() {
+  let file = File {
+    name: "a".to_string(),
+    mode: Permissions(0b101),
+  };
+  let msg: FileMessage = s2_grpc_utils::S2ProtoPackRef::pack_ref(&file).unwrap();
+  assert_eq!(
+    msg,
+    FileMessage {
+      name: "a".to_string(),
+      mode: 5,
+    }
+  );
+  let unpacked: File = s2_grpc_utils::S2ProtoUnpackRef::unpack_ref(&msg).unwrap();
+  assert_eq!(unpacked, file);
+  let packed: FileMessage = file.pack().unwrap();
+  assert_eq!(packed, msg);
+
+  let err = File::unpack(FileMessage {
+    name: "a".to_string(),
+    mode: 8,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "at mode: Integer value 8 does not fit in Permissions"
+  );
+}
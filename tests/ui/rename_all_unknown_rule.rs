@@ -0,0 +1,13 @@
+use s2_grpc_utils::S2ProtoUnpack;
+
+struct AccountMessage {
+  id: i64,
+}
+
+#[derive(S2ProtoUnpack)]
+#[s2_grpc(message_type = "AccountMessage", rename_all = "kebab-case")]
+struct Account {
+  id: i64,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use s2_grpc_utils::S2ProtoPack;
+
+struct UserMessage {
+  user_id: Option<i64>,
+}
+
+#[derive(S2ProtoPack)]
+#[s2_grpc(message_type = "UserMessage")]
+struct User {
+  #[s2_grpc(required, optional)]
+  user_id: i64,
+}
+
+fn main() {}
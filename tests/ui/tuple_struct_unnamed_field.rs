@@ -0,0 +1,12 @@
+use s2_grpc_utils::S2ProtoPack;
+
+struct PointMessage {
+  x: i32,
+  y: i32,
+}
+
+#[derive(S2ProtoPack)]
+#[s2_grpc(message_type = "PointMessage")]
+struct Point(#[s2_grpc(rename = "x")] i32, i32);
+
+fn main() {}
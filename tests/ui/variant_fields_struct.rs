@@ -0,0 +1,14 @@
+use s2_grpc_utils::S2ProtoPack;
+
+#[derive(Default)]
+pub struct Contact {
+  pub phone: String,
+}
+
+#[derive(S2ProtoPack)]
+#[s2_grpc(message_type = "Contact", variant_fields)]
+struct ContactKind {
+  phone: String,
+}
+
+fn main() {}
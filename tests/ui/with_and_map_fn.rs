@@ -0,0 +1,24 @@
+use s2_grpc_utils::S2ProtoPack;
+
+struct UserMessage {
+  user_id: i64,
+}
+
+mod id {
+  pub fn pack(value: i64) -> s2_grpc_utils::result::Result<i64> {
+    Ok(value)
+  }
+}
+
+fn map_id(value: i64) -> i64 {
+  value
+}
+
+#[derive(S2ProtoPack)]
+#[s2_grpc(message_type = "UserMessage")]
+struct User {
+  #[s2_grpc(with = "id", map_fn = "map_id")]
+  user_id: i64,
+}
+
+fn main() {}